@@ -14,27 +14,35 @@
 //  You should have received a copy of the GNU General Public License
 //  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
 
-use std::io::{Error, ErrorKind};
-use std::fs::File;
+use std::io::Error;
 use std::rc::Rc;
 
 use thrust_ui::input::{Cursor, Event, EventKind, MouseButton};
 use thrust_ui::theme_builder::ThemeBuilderSet;
 use thrust_ui::widget_tree::WidgetTree;
-use thrust_ui::widget::{EmptyWidget, Renderer, Widget};
+use thrust_ui::color::Color;
+use thrust_ui::render::{Renderer, TextRun, Texture, UvRect};
+use thrust_ui::widget::{EmptyWidget, Rect, Size, Widget};
 use thrust_ui::button::Button;
 
 struct DefaultRenderer { }
 
 impl Renderer for DefaultRenderer {
-    fn render(&mut self) { }
+    fn fill_rect(&mut self, _rect: Rect, _color: Color) { }
+
+    fn draw_quad(&mut self, _texture: &Texture, _rect: Rect, _uv: UvRect, _color: Color) { }
+
+    fn draw_text(&mut self, run: &TextRun) {
+        println!("{}", run.text);
+    }
+
+    fn push_clip(&mut self, _rect: Rect) { }
+
+    fn pop_clip(&mut self) { }
 }
 
 fn main() -> Result<(), Error> {
-    let theme_file = File::open("theme.yml")?;
-    let theme_builder: ThemeBuilderSet = serde_yaml::from_reader(theme_file).
-        map_err(|err| Error::new(ErrorKind::InvalidInput, err.to_string()))?;
-    let theme = theme_builder.create_theme_set()?;
+    let theme = ThemeBuilderSet::load("theme.yml")?.create_theme_set()?;
 
     let mut root_widget = EmptyWidget::new();
     root_widget.set_theme("root");
@@ -58,9 +66,12 @@ fn main() -> Result<(), Error> {
     tree.add_child(root, button1);
     tree.add_child(root, button2);
 
+    tree.set_size(Size { width: 320, height: 240 });
+    tree.layout();
+
     let evt = Event {
         kind: EventKind::MousePressed { button: MouseButton::Left },
-        cursor: Cursor { x: 0.0, y: 0.0 }
+        cursor: Cursor { x: 2.0, y: 2.0 }
     };
 
     tree.draw(&mut renderer);
@@ -0,0 +1,544 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+//! Loading of AngelCode BMFont bitmap fonts.  Both the text and binary
+//! (version 3) descriptor formats are supported, with glyph pages loaded from
+//! PNG files relative to the descriptor.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::color::Color;
+use crate::font::Font;
+use crate::png_image::load_texture;
+use crate::render::{Renderer, Texture, UvRect};
+use crate::widget::Rect;
+
+/// A single glyph, as a region of one of the font's page textures.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BitmapGlyph {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+    pub x_advance: i32,
+    pub page: usize,
+}
+
+/// The parsed contents of a BMFont descriptor, before the pages are loaded.
+#[derive(Debug, Default)]
+pub struct BitmapFontDescriptor {
+    pub line_height: u32,
+    pub base: u32,
+    pub pages: Vec<String>,
+    pub glyphs: HashMap<char, BitmapGlyph>,
+    pub kerning: HashMap<(char, char), i32>,
+}
+
+impl BitmapFontDescriptor {
+    /// Parses either a text or binary BMFont descriptor from `data`.
+    pub fn parse(data: &[u8]) -> Result<BitmapFontDescriptor, String> {
+        if data.starts_with(b"BMF") {
+            parse_binary(data)
+        } else {
+            let text = std::str::from_utf8(data).map_err(|err| err.to_string())?;
+            parse_text(text)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BitmapFont {
+    line_height: f32,
+    baseline: f32,
+    glyphs: HashMap<char, BitmapGlyph>,
+    kerning: HashMap<(char, char), f32>,
+    pages: Vec<Rc<Texture>>,
+}
+
+impl BitmapFont {
+    /// Creates a font from the `descriptor`, with one texture in `pages` for each
+    /// page in the descriptor.
+    pub fn new(descriptor: BitmapFontDescriptor,
+               pages: Vec<Rc<Texture>>) -> Result<BitmapFont, Error> {
+        if pages.len() != descriptor.pages.len() {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("Font has {} pages but {} textures were specified",
+                                          descriptor.pages.len(), pages.len())));
+        }
+
+        if let Some((c, glyph)) = descriptor.glyphs.iter().find(|(_, g)| g.page >= pages.len()) {
+            return Err(Error::new(ErrorKind::InvalidData,
+                                  format!("Glyph '{}' refers to invalid page {}", c, glyph.page)));
+        }
+
+        Ok(BitmapFont {
+            line_height: descriptor.line_height as f32,
+            baseline: descriptor.base as f32,
+            glyphs: descriptor.glyphs,
+            kerning: descriptor.kerning.into_iter().map(|(k, v)| (k, v as f32)).collect(),
+            pages,
+        })
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&BitmapGlyph> {
+        self.glyphs.get(&c)
+    }
+
+    pub fn pages(&self) -> &[Rc<Texture>] { &self.pages }
+}
+
+impl Font for BitmapFont {
+    fn line_height(&self) -> f32 { self.line_height }
+
+    fn baseline(&self) -> f32 { self.baseline }
+
+    fn advance(&self, c: char) -> f32 {
+        self.glyphs.get(&c).map_or(0.0, |glyph| glyph.x_advance as f32)
+    }
+
+    fn kerning(&self, left: char, right: char) -> f32 {
+        self.kerning.get(&(left, right)).copied().unwrap_or(0.0)
+    }
+
+    fn draw_glyph(&self, renderer: &mut dyn Renderer, c: char, x: f32, y: f32,
+                  scale: f32, color: Color) {
+        let glyph = match self.glyphs.get(&c) {
+            None => return,
+            Some(glyph) => glyph,
+        };
+        if glyph.width == 0 || glyph.height == 0 { return; }
+
+        let texture = &self.pages[glyph.page];
+        let region = Rect { x: glyph.x as i32, y: glyph.y as i32,
+            width: glyph.width, height: glyph.height };
+        let rect = Rect {
+            x: (x + glyph.x_offset as f32 * scale).round() as i32,
+            y: (y + glyph.y_offset as f32 * scale).round() as i32,
+            width: (glyph.width as f32 * scale).round() as u32,
+            height: (glyph.height as f32 * scale).round() as u32,
+        };
+        let uv = UvRect::from_pixels(region, texture.width(), texture.height());
+        renderer.draw_quad(texture, rect, uv, color);
+    }
+}
+
+/// Loads the BMFont descriptor at `path`, in either the text or binary format,
+/// along with all of its PNG pages.  Page files are relative to the descriptor.
+pub fn load_bitmap_font<P: AsRef<Path>>(path: P) -> Result<BitmapFont, Error> {
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(|err| {
+        Error::new(err.kind(), format!("{}: {}", path.display(), err))
+    })?;
+    let descriptor = BitmapFontDescriptor::parse(&data).map_err(|err| font_error(path, err))?;
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut pages = Vec::new();
+    for page in descriptor.pages.iter() {
+        pages.push(Rc::new(load_texture(dir.join(page))?));
+    }
+
+    BitmapFont::new(descriptor, pages).map_err(|err| font_error(path, err))
+}
+
+fn font_error<E: std::fmt::Display>(path: &Path, err: E) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
+}
+
+fn to_char(id: u32) -> Result<char, String> {
+    std::char::from_u32(id).ok_or_else(|| format!("Invalid character id {}", id))
+}
+
+fn parse_text(text: &str) -> Result<BitmapFontDescriptor, String> {
+    let mut out = BitmapFontDescriptor::default();
+    let mut page_count = MAX_PAGES;
+
+    for (line_index, line) in text.lines().enumerate() {
+        let (tag, values) = match split_tag(line) {
+            None => continue,
+            Some(tag) => tag,
+        };
+        let values = parse_values(values);
+        let get = |key: &str| -> Result<i32, String> {
+            match values.get(key) {
+                None => Ok(0),
+                Some(value) => value.parse::<i32>().map_err(|_| {
+                    format!("line {}: Invalid value '{}' for '{}'", line_index + 1, value, key)
+                }),
+            }
+        };
+
+        match tag {
+            "common" => {
+                out.line_height = get("lineHeight")?.max(0) as u32;
+                out.base = get("base")?.max(0) as u32;
+                if values.contains_key("pages") {
+                    page_count = (get("pages")?.max(0) as usize).min(MAX_PAGES);
+                }
+            },
+            "page" => {
+                let id = get("id")?.max(0) as usize;
+                if id >= page_count {
+                    return Err(format!("line {}: Invalid page id {}", line_index + 1, id));
+                }
+                let file = values.get("file").cloned().unwrap_or_default();
+                if out.pages.len() <= id { out.pages.resize(id + 1, String::new()); }
+                out.pages[id] = file;
+            },
+            "char" => {
+                let c = to_char(get("id")? as u32)?;
+                out.glyphs.insert(c, BitmapGlyph {
+                    x: get("x")?.max(0) as u32,
+                    y: get("y")?.max(0) as u32,
+                    width: get("width")?.max(0) as u32,
+                    height: get("height")?.max(0) as u32,
+                    x_offset: get("xoffset")?,
+                    y_offset: get("yoffset")?,
+                    x_advance: get("xadvance")?,
+                    page: get("page")?.max(0) as usize,
+                });
+            },
+            "kerning" => {
+                let first = to_char(get("first")? as u32)?;
+                let second = to_char(get("second")? as u32)?;
+                out.kerning.insert((first, second), get("amount")?);
+            },
+            _ => (),
+        }
+    }
+
+    if out.pages.iter().any(|page| page.is_empty()) {
+        return Err("Missing page definition".to_string());
+    }
+
+    Ok(out)
+}
+
+fn split_tag(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.is_empty() { return None; }
+
+    match line.find(char::is_whitespace) {
+        None => Some((line, "")),
+        Some(index) => Some((&line[..index], &line[index..])),
+    }
+}
+
+// parses whitespace separated key=value pairs, where values may be quoted
+fn parse_values(input: &str) -> HashMap<&str, String> {
+    let mut values = HashMap::new();
+    let mut rest = input.trim_start();
+
+    while !rest.is_empty() {
+        let key_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = &rest[key_end..];
+
+        if !rest.starts_with('=') {
+            rest = rest.trim_start();
+            continue;
+        }
+        rest = &rest[1..];
+
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            rest = quoted.get(end + 1..).unwrap_or("");
+            &quoted[..end]
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let value = &rest[..end];
+            rest = &rest[end..];
+            value
+        };
+
+        values.insert(key, value.to_string());
+        rest = rest.trim_start();
+    }
+
+    values
+}
+
+const BINARY_VERSION: u8 = 3;
+const BLOCK_COMMON: u8 = 2;
+const BLOCK_PAGES: u8 = 3;
+const BLOCK_CHARS: u8 = 4;
+const BLOCK_KERNING: u8 = 5;
+const CHAR_SIZE: usize = 20;
+const KERNING_SIZE: usize = 10;
+
+// page ids are stored in a single byte in the binary format
+const MAX_PAGES: usize = 256;
+
+fn parse_binary(data: &[u8]) -> Result<BitmapFontDescriptor, String> {
+    if data.len() < 4 || data[3] != BINARY_VERSION {
+        return Err(format!("Unsupported binary font version, only version {} is supported",
+                           BINARY_VERSION));
+    }
+
+    let mut out = BitmapFontDescriptor::default();
+    let mut page_count = 0;
+    let mut rest = &data[4..];
+    while !rest.is_empty() {
+        if rest.len() < 5 { return Err("Truncated block header".to_string()); }
+        let kind = rest[0];
+        let size = u32::from_le_bytes(rest[1..5].try_into().unwrap()) as usize;
+        rest = &rest[5..];
+        if rest.len() < size {
+            return Err(format!("Block {} is truncated", kind));
+        }
+        let block = &rest[..size];
+        rest = &rest[size..];
+
+        match kind {
+            BLOCK_COMMON => {
+                if block.len() < 10 { return Err("Common block is too short".to_string()); }
+                out.line_height = read_u16(block, 0) as u32;
+                out.base = read_u16(block, 2) as u32;
+                page_count = read_u16(block, 8) as usize;
+            },
+            BLOCK_PAGES => {
+                out.pages = block.split(|b| *b == 0)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| String::from_utf8_lossy(name).to_string())
+                    .collect();
+            },
+            BLOCK_CHARS => {
+                let entries = block.chunks_exact(CHAR_SIZE);
+                if !entries.remainder().is_empty() {
+                    return Err("Chars block is truncated".to_string());
+                }
+                for entry in entries {
+                    let c = to_char(read_u32(entry, 0))?;
+                    out.glyphs.insert(c, BitmapGlyph {
+                        x: read_u16(entry, 4) as u32,
+                        y: read_u16(entry, 6) as u32,
+                        width: read_u16(entry, 8) as u32,
+                        height: read_u16(entry, 10) as u32,
+                        x_offset: read_u16(entry, 12) as i16 as i32,
+                        y_offset: read_u16(entry, 14) as i16 as i32,
+                        x_advance: read_u16(entry, 16) as i16 as i32,
+                        page: entry[18] as usize,
+                    });
+                }
+            },
+            BLOCK_KERNING => {
+                let entries = block.chunks_exact(KERNING_SIZE);
+                if !entries.remainder().is_empty() {
+                    return Err("Kerning block is truncated".to_string());
+                }
+                for entry in entries {
+                    let first = to_char(read_u32(entry, 0))?;
+                    let second = to_char(read_u32(entry, 4))?;
+                    out.kerning.insert((first, second), read_u16(entry, 8) as i16 as i32);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    if out.pages.len() != page_count {
+        return Err(format!("Expected {} pages but found {}", page_count, out.pages.len()));
+    }
+
+    Ok(out)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT_FONT: &str = "\
+info face=\"Test Font\" size=12 bold=0
+common lineHeight=14 base=11 scaleW=64 scaleH=64 pages=2
+page id=0 file=\"page 0.png\"
+page id=1 file=\"page1.png\"
+chars count=3
+char id=65 x=1 y=2 width=6 height=9 xoffset=0 yoffset=2 xadvance=7 page=0
+char id=86 x=10 y=0 width=7 height=9 xoffset=-1 yoffset=2 xadvance=6 page=1
+char id=32 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=4 page=0
+kernings count=1
+kerning first=65 second=86 amount=-2
+";
+
+    fn pages(count: usize) -> Vec<Rc<Texture>> {
+        (0..count).map(|_| Rc::new(Texture::new(1, 1, vec![0; 4]))).collect()
+    }
+
+    fn u16_bytes(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect()
+    }
+
+    fn block(kind: u8, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![kind];
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn binary_char(id: u32, values: [u16; 7], page: u8) -> Vec<u8> {
+        let mut out = id.to_le_bytes().to_vec();
+        out.extend(u16_bytes(&values));
+        out.extend_from_slice(&[page, 15]);
+        out
+    }
+
+    // a binary version of TEXT_FONT, as a list of blocks
+    fn binary_blocks() -> Vec<Vec<u8>> {
+        let mut common = u16_bytes(&[14, 11, 64, 64, 2]);
+        common.extend_from_slice(&[0, 0, 0, 0, 0]);
+
+        let mut chars = binary_char(65, [1, 2, 6, 9, 0, 2, 7], 0);
+        chars.extend(binary_char(86, [10, 0, 7, 9, -1i16 as u16, 2, 6], 1));
+        chars.extend(binary_char(32, [0, 0, 0, 0, 0, 0, 4], 0));
+
+        let mut kerning = 65u32.to_le_bytes().to_vec();
+        kerning.extend_from_slice(&86u32.to_le_bytes());
+        kerning.extend_from_slice(&(-2i16).to_le_bytes());
+
+        vec![
+            block(BLOCK_COMMON, &common),
+            block(BLOCK_PAGES, b"page 0.png\0page1.png\0"),
+            block(BLOCK_CHARS, &chars),
+            block(BLOCK_KERNING, &kerning),
+        ]
+    }
+
+    fn binary(blocks: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = b"BMF\x03".to_vec();
+        for block in blocks {
+            out.extend_from_slice(block);
+        }
+        out
+    }
+
+    fn check_descriptor(descriptor: &BitmapFontDescriptor) {
+        assert_eq!(descriptor.line_height, 14);
+        assert_eq!(descriptor.base, 11);
+        assert_eq!(descriptor.pages, vec!["page 0.png", "page1.png"]);
+        assert_eq!(descriptor.glyphs.len(), 3);
+
+        let glyph = descriptor.glyphs[&'V'];
+        assert_eq!((glyph.x, glyph.y, glyph.width, glyph.height), (10, 0, 7, 9));
+        assert_eq!((glyph.x_offset, glyph.y_offset, glyph.x_advance), (-1, 2, 6));
+        assert_eq!(glyph.page, 1);
+        assert_eq!(descriptor.glyphs[&'A'].page, 0);
+        assert_eq!(descriptor.kerning.get(&('A', 'V')), Some(&-2));
+    }
+
+    #[test]
+    fn parse_text_descriptor() {
+        check_descriptor(&BitmapFontDescriptor::parse(TEXT_FONT.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn parse_binary_descriptor() {
+        check_descriptor(&BitmapFontDescriptor::parse(&binary(&binary_blocks())).unwrap());
+    }
+
+    #[test]
+    fn kerning_is_applied_to_line_width() {
+        let descriptor = BitmapFontDescriptor::parse(TEXT_FONT.as_bytes()).unwrap();
+        let font = BitmapFont::new(descriptor, pages(2)).unwrap();
+
+        assert_eq!(font.line_width("AV", 1.0), 7.0 + 6.0 - 2.0);
+        assert_eq!(font.line_width("VA A", 2.0), (6.0 + 7.0 + 4.0 + 7.0) * 2.0);
+        assert_eq!(font.advance('x'), 0.0);
+    }
+
+    #[test]
+    fn text_descriptor_errors() {
+        let missing_page = TEXT_FONT.replace("page id=0 file=\"page 0.png\"\n", "");
+        assert_eq!(BitmapFontDescriptor::parse(missing_page.as_bytes()).unwrap_err(),
+                   "Missing page definition");
+
+        let bad_value = TEXT_FONT.replace("xadvance=7", "xadvance=seven");
+        assert_eq!(BitmapFontDescriptor::parse(bad_value.as_bytes()).unwrap_err(),
+                   "line 6: Invalid value 'seven' for 'xadvance'");
+
+        let huge_page = TEXT_FONT.replace("page id=1", "page id=2000000000");
+        assert_eq!(BitmapFontDescriptor::parse(huge_page.as_bytes()).unwrap_err(),
+                   "line 4: Invalid page id 2000000000");
+
+        let extra_page = TEXT_FONT.replace("page id=1", "page id=2");
+        assert_eq!(BitmapFontDescriptor::parse(extra_page.as_bytes()).unwrap_err(),
+                   "line 4: Invalid page id 2");
+
+        let no_count = TEXT_FONT.replace(" pages=2", "").replace("page id=1", "page id=300");
+        assert_eq!(BitmapFontDescriptor::parse(no_count.as_bytes()).unwrap_err(),
+                   "line 4: Invalid page id 300");
+    }
+
+    #[test]
+    fn truncated_binary_blocks() {
+        let mut data = binary(&binary_blocks());
+        data.truncate(data.len() - 1);
+        assert_eq!(BitmapFontDescriptor::parse(&data).unwrap_err(), "Block 5 is truncated");
+
+        let mut blocks = binary_blocks();
+        blocks.truncate(3);
+        let mut data = binary(&blocks);
+        data.extend_from_slice(&[BLOCK_KERNING, 0]);
+        assert_eq!(BitmapFontDescriptor::parse(&data).unwrap_err(), "Truncated block header");
+
+        let mut blocks = binary_blocks();
+        blocks[2] = block(BLOCK_CHARS, &binary_char(65, [0; 7], 0)[..CHAR_SIZE - 1]);
+        assert_eq!(BitmapFontDescriptor::parse(&binary(&blocks)).unwrap_err(),
+                   "Chars block is truncated");
+
+        blocks[0] = block(BLOCK_COMMON, &[14, 0, 11, 0]);
+        assert_eq!(BitmapFontDescriptor::parse(&binary(&blocks)).unwrap_err(),
+                   "Common block is too short");
+    }
+
+    #[test]
+    fn binary_version_and_page_count() {
+        let mut data = binary(&binary_blocks());
+        data[3] = 2;
+        assert!(BitmapFontDescriptor::parse(&data).unwrap_err().contains("version 3"));
+
+        let mut blocks = binary_blocks();
+        blocks[1] = block(BLOCK_PAGES, b"page 0.png\0");
+        assert_eq!(BitmapFontDescriptor::parse(&binary(&blocks)).unwrap_err(),
+                   "Expected 2 pages but found 1");
+    }
+
+    #[test]
+    fn bad_page_indices() {
+        let descriptor = BitmapFontDescriptor::parse(TEXT_FONT.as_bytes()).unwrap();
+        let err = BitmapFont::new(descriptor, pages(1)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        let bad_page = TEXT_FONT.replace("xadvance=6 page=1", "xadvance=6 page=2");
+        let descriptor = BitmapFontDescriptor::parse(bad_page.as_bytes()).unwrap();
+        let err = BitmapFont::new(descriptor, pages(2)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Glyph 'V' refers to invalid page 2");
+    }
+}
@@ -16,36 +16,64 @@
 
 use std::any::Any;
 
-use crate::widget::{Widget, WidgetState};
-use crate::widget::Renderer;
+use crate::render::Renderer;
+use crate::text_layout::TextLayout;
+use crate::widget::{Size, Widget, WidgetState};
 
 widget!{
     #[derive(Default)]
     pub struct Button {
-        text: String
+        text: Option<String>,
+        text_layout: TextLayout
     }
 
     fn on_add(&mut self) {}
 
-    fn draw(&self, renderer: &mut Renderer) {
+    fn draw(&self, renderer: &mut dyn Renderer) {
         self.state().draw(renderer);
-        println!("{}", self.text);
+        self.text_layout.draw(renderer);
+    }
+
+    fn layout(&mut self) {
+        self.text_layout = TextLayout::new(self.state.font(), self.text(),
+                                           self.state.text_params(), self.state.inner());
+    }
+
+    fn content_size(&self) -> Size {
+        self.state().measure_text(self.text())
     }
 }
 
 impl Button {
     pub fn new(text: String) -> Button {
         let mut button = Button {
-            text,
+            text: Some(text),
             ..Default::default()
         };
 
         button.set_theme("button");
+        button.state.set_focusable(true);
 
         button
     }
 
+    /// Returns the text shown by this button.  As with `Label`, text set in code
+    /// takes precedence over the theme `text`.
     pub fn text(&self) -> &str {
-        &self.text
+        match &self.text {
+            Some(text) => text,
+            None => self.state.theme_text().unwrap_or_default(),
+        }
+    }
+
+    pub fn set_text<S: Into<String>>(&mut self, text: S) {
+        self.text = Some(text.into());
+        self.state.invalidate_layout();
+    }
+
+    /// Removes any text set in code, so the theme `text` is shown instead
+    pub fn reset_text(&mut self) {
+        self.text = None;
+        self.state.invalidate_layout();
     }
 }
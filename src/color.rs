@@ -35,7 +35,7 @@ impl Default for Color {
 }
 
 fn get_component(text: &str, max: f32) -> Result<f32, Error> {
-    let component = i32::from_str_radix(&text, 16);
+    let component = i32::from_str_radix(text, 16);
     match component {
         Err(_) => Err(Error::new(ErrorKind::InvalidInput,
                                  format!("Unable to parse color component from '{}'", text))),
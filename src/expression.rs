@@ -0,0 +1,325 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+/// The values that may be referenced by name in an `Expression`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variable {
+    ParentWidth,
+    ParentHeight,
+    Width,
+    Height,
+}
+
+impl FromStr for Variable {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        use self::Variable::*;
+        Ok(match text {
+            "parent.width" => ParentWidth,
+            "parent.height" => ParentHeight,
+            "width" => Width,
+            "height" => Height,
+            _ => return Err(Error::new(ErrorKind::InvalidInput,
+                                       format!("Unknown variable '{}' in expression", text))),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+/// An arithmetic expression used by themes to compute sizes and positions,
+/// such as `50%` or `parent.width - 20`.  Percentages are relative to the
+/// parent's inner size along the axis being computed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f32),
+    Percent(f32),
+    Variable(Variable),
+    Negate(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+/// The values available while evaluating an `Expression`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Variables {
+    pub parent_width: f32,
+    pub parent_height: f32,
+    pub width: f32,
+    pub height: f32,
+
+    /// The value that `100%` refers to
+    pub percent_of: f32,
+}
+
+impl Expression {
+    pub fn evaluate(&self, vars: &Variables) -> f32 {
+        use self::Expression::*;
+        match self {
+            Number(value) => *value,
+            Percent(value) => value / 100.0 * vars.percent_of,
+            Variable(var) => match var {
+                self::Variable::ParentWidth => vars.parent_width,
+                self::Variable::ParentHeight => vars.parent_height,
+                self::Variable::Width => vars.width,
+                self::Variable::Height => vars.height,
+            },
+            Negate(expr) => -expr.evaluate(vars),
+            Binary(op, left, right) => {
+                let left = left.evaluate(vars);
+                let right = right.evaluate(vars);
+                match op {
+                    Operator::Add => left + right,
+                    Operator::Subtract => left - right,
+                    Operator::Multiply => left * right,
+                    Operator::Divide => if right == 0.0 { 0.0 } else { left / right },
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens: &tokens, pos: 0, text };
+        let expr = parser.sum()?;
+
+        if parser.pos != tokens.len() {
+            return Err(parser.error("Unexpected trailing input"));
+        }
+
+        Ok(expr)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Percent,
+    Op(Operator),
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            ' ' | '\t' => continue,
+            '+' => Token::Op(Operator::Add),
+            '-' => Token::Op(Operator::Subtract),
+            '*' => Token::Op(Operator::Multiply),
+            '/' => Token::Op(Operator::Divide),
+            '%' => Token::Percent,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c if c.is_ascii_digit() || c == '.' => {
+                let end = take_while(&mut chars, start, |c| c.is_ascii_digit() || c == '.');
+                let value = f32::from_str(&text[start..end]).map_err(|_| {
+                    Error::new(ErrorKind::InvalidInput,
+                               format!("Invalid number '{}' in expression '{}'",
+                                       &text[start..end], text))
+                })?;
+                Token::Number(value)
+            },
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let end = take_while(&mut chars, start,
+                                     |c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+                Token::Ident(text[start..end].to_string())
+            },
+            _ => return Err(Error::new(ErrorKind::InvalidInput,
+                                       format!("Unexpected '{}' in expression '{}'", c, text))),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+// consumes characters matching `pred`, returning the end index of the token
+// beginning at `start`
+fn take_while<F: Fn(char) -> bool>(chars: &mut std::iter::Peekable<std::str::CharIndices>,
+                                   start: usize, pred: F) -> usize {
+    let mut end = start + 1;
+    while let Some(&(index, c)) = chars.peek() {
+        if !pred(c) { break; }
+        end = index + c.len_utf8();
+        chars.next();
+    }
+    end
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    text: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> Error {
+        Error::new(ErrorKind::InvalidInput, format!("{} in expression '{}'", message, self.text))
+    }
+
+    fn next_op(&mut self, ops: &[Operator]) -> Option<Operator> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                self.pos += 1;
+                Some(*op)
+            },
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expression, Error> {
+        let mut expr = self.product()?;
+        while let Some(op) = self.next_op(&[Operator::Add, Operator::Subtract]) {
+            let right = self.product()?;
+            expr = Expression::Binary(op, Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expression, Error> {
+        let mut expr = self.unary()?;
+        while let Some(op) = self.next_op(&[Operator::Multiply, Operator::Divide]) {
+            let right = self.unary()?;
+            expr = Expression::Binary(op, Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expression, Error> {
+        if self.next_op(&[Operator::Subtract]).is_some() {
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expression, Error> {
+        let token = match self.tokens.get(self.pos) {
+            None => return Err(self.error("Unexpected end")),
+            Some(token) => token.clone(),
+        };
+        self.pos += 1;
+
+        match token {
+            Token::Number(value) => {
+                if self.tokens.get(self.pos) == Some(&Token::Percent) {
+                    self.pos += 1;
+                    Ok(Expression::Percent(value))
+                } else {
+                    Ok(Expression::Number(value))
+                }
+            },
+            Token::Ident(ident) => Ok(Expression::Variable(Variable::from_str(&ident)?)),
+            Token::Open => {
+                let expr = self.sum()?;
+                if self.tokens.get(self.pos) != Some(&Token::Close) {
+                    return Err(self.error("Expected ')'"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            },
+            _ => Err(self.error("Unexpected token")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str) -> f32 {
+        let vars = Variables {
+            parent_width: 200.0,
+            parent_height: 100.0,
+            width: 50.0,
+            height: 20.0,
+            percent_of: 80.0,
+        };
+        Expression::from_str(text).unwrap().evaluate(&vars)
+    }
+
+    fn parse_error(text: &str) -> String {
+        let err = Expression::from_str(text).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        err.to_string()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("parent.width - 20 * 2"), 160.0);
+        assert_eq!(eval("10 + 6 / 2 - 1"), 12.0);
+        assert_eq!(eval("10 - 4 - 3"), 3.0);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-5"), -5.0);
+        assert_eq!(eval("10 - -5"), 15.0);
+        assert_eq!(eval("-width * 2"), -100.0);
+        assert_eq!(eval("--3"), 3.0);
+    }
+
+    #[test]
+    fn percent() {
+        assert_eq!(eval("50%"), 40.0);
+        assert_eq!(eval("100% - height"), 60.0);
+        assert_eq!(Expression::from_str("50%").unwrap(), Expression::Percent(50.0));
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(eval("(parent.width - 20) * 2"), 360.0);
+        assert_eq!(eval("((1 + 2)) * (parent.height / 4)"), 75.0);
+        assert!(parse_error("(1 + 2").contains("Expected ')'"));
+        assert!(parse_error("1 + )").contains("Unexpected token"));
+    }
+
+    #[test]
+    fn unknown_variable() {
+        assert!(parse_error("parent.depth + 1").contains("Unknown variable 'parent.depth'"));
+    }
+
+    #[test]
+    fn trailing_input() {
+        assert!(parse_error("1 2").contains("Unexpected trailing input"));
+        assert!(parse_error("width)").contains("Unexpected trailing input"));
+        assert!(parse_error("1 +").contains("Unexpected end"));
+        assert!(parse_error("1 $ 2").contains("Unexpected '$'"));
+    }
+
+    #[test]
+    fn division_by_zero_is_zero() {
+        assert_eq!(eval("10 / 0"), 0.0);
+        assert_eq!(eval("width / (height - 20)"), 0.0);
+    }
+}
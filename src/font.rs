@@ -0,0 +1,162 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use log::warn;
+
+use crate::color::Color;
+use crate::render::Renderer;
+use crate::theme::ThemeSet;
+use crate::widget::Size;
+
+/// A source of glyph metrics and glyph images.  All metrics are in pixels at
+/// a scale of `1.0`, and are multiplied by the `TextParams` scale when text
+/// is measured or drawn.
+pub trait Font {
+    /// The vertical distance between consecutive lines of text
+    fn line_height(&self) -> f32;
+
+    /// The distance from the top of a line to the baseline
+    fn baseline(&self) -> f32;
+
+    /// The horizontal distance the pen moves after drawing the glyph for `c`
+    fn advance(&self, c: char) -> f32;
+
+    /// The adjustment to the advance between the `left` and `right` glyphs
+    fn kerning(&self, _left: char, _right: char) -> f32 { 0.0 }
+
+    /// Draws the glyph for `c` with the pen at `x`, and the top of the line at `y`
+    fn draw_glyph(&self, _renderer: &mut dyn Renderer, _c: char, _x: f32, _y: f32,
+                  _scale: f32, _color: Color) { }
+
+    /// Returns the width of a single line of `text`
+    fn line_width(&self, text: &str, scale: f32) -> f32 {
+        let mut width = 0.0;
+        let mut prev = None;
+        for c in text.chars() {
+            if let Some(prev) = prev {
+                width += self.kerning(prev, c);
+            }
+            width += self.advance(c);
+            prev = Some(c);
+        }
+        width * scale
+    }
+
+    /// Returns the size of the area covered by `text`, which may contain
+    /// multiple lines separated by `\n`
+    fn measure(&self, text: &str, scale: f32) -> Size {
+        if text.is_empty() { return Size::default(); }
+
+        let mut lines = 0;
+        let mut width: f32 = 0.0;
+        for line in text.split('\n') {
+            lines += 1;
+            width = width.max(self.line_width(line, scale));
+        }
+
+        Size {
+            width: width.ceil() as u32,
+            height: (lines as f32 * self.line_height() * scale).ceil() as u32,
+        }
+    }
+
+    /// Draws a single line of `text` with the top left of the line at `x`, `y`
+    fn draw_line(&self, renderer: &mut dyn Renderer, text: &str, x: f32, y: f32,
+                 scale: f32, color: Color) {
+        let mut pen = x;
+        let mut prev = None;
+        for c in text.chars() {
+            if let Some(prev) = prev {
+                pen += self.kerning(prev, c) * scale;
+            }
+            self.draw_glyph(renderer, c, pen, y, scale, color);
+            pen += self.advance(c) * scale;
+            prev = Some(c);
+        }
+    }
+}
+
+impl fmt::Debug for dyn Font {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Font {{ line_height: {}, baseline: {} }}", self.line_height(), self.baseline())
+    }
+}
+
+/// A font with no glyphs, used when a theme refers to a font that is not
+/// in the registry.
+pub struct EmptyFont { }
+
+impl Font for EmptyFont {
+    fn line_height(&self) -> f32 { 0.0 }
+
+    fn baseline(&self) -> f32 { 0.0 }
+
+    fn advance(&self, _c: char) -> f32 { 0.0 }
+}
+
+pub const DEFAULT_FONT_ID: &str = "default";
+
+/// The set of named fonts that themes may refer to with `text_params.font`.
+#[derive(Default)]
+pub struct FontRegistry {
+    fonts: HashMap<String, Rc<dyn Font>>,
+}
+
+impl FontRegistry {
+    pub fn new() -> FontRegistry {
+        FontRegistry::default()
+    }
+
+    /// Adds the `font` with the specified `id`, replacing any existing font with
+    /// that ID.  The font with ID `default` is used for any missing fonts.
+    pub fn insert<S: Into<String>>(&mut self, id: S, font: Rc<dyn Font>) {
+        self.fonts.insert(id.into(), font);
+    }
+
+    /// Adds all fonts loaded from the `fonts` section of the theme file.
+    pub fn insert_theme_fonts(&mut self, themes: &ThemeSet) {
+        for (id, font) in themes.fonts() {
+            self.insert(id.to_string(), Rc::clone(font));
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.fonts.contains_key(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Rc<dyn Font>> {
+        self.fonts.get(id).cloned()
+    }
+
+    /// Returns the font for the specified `id`, or the default font if it is not
+    /// in this registry.  If there is no default font either, returns an `EmptyFont`.
+    pub fn font(&self, id: &str) -> Rc<dyn Font> {
+        if let Some(font) = self.get(id) { return font; }
+
+        if let Some(font) = self.get(DEFAULT_FONT_ID) {
+            return font;
+        }
+
+        if !self.fonts.is_empty() {
+            warn!("Font '{}' is not in the font registry", id);
+        }
+        Rc::new(EmptyFont { })
+    }
+}
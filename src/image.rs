@@ -14,12 +14,21 @@
 //  You should have received a copy of the GNU General Public License
 //  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
 
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
 use std::rc::Rc;
 
-use crate::widget::{Point, Size, Renderer};
+use log::warn;
 
+use crate::color::Color;
+use crate::render::{Renderer, Texture, UvRect};
+use crate::theme::{Border, ImageDefinition, ThemeSet};
+use crate::widget::{Point, Rect, Size};
+
+#[derive(Clone)]
 pub struct Image {
-    source: Rc<ImageSource>,
+    source: Rc<dyn ImageSource>,
 }
 
 impl Default for Image {
@@ -31,15 +40,20 @@ impl Default for Image {
 }
 
 impl Image {
-    pub fn new(source: Rc<ImageSource>) -> Image {
+    pub fn new(source: Rc<dyn ImageSource>) -> Image {
         Image {
-            source: source,
+            source,
         }
     }
 
-    pub fn draw(&self, renderer: &mut Renderer, position: Point, size: Size) {
+    pub fn draw(&self, renderer: &mut dyn Renderer, position: Point, size: Size) {
         self.source.draw(renderer, position, size);
     }
+
+    /// The natural size of this image in pixels, if it has one
+    pub fn size(&self) -> Option<Size> {
+        self.source.size()
+    }
 }
 
 struct EmptyImage { }
@@ -47,5 +61,300 @@ struct EmptyImage { }
 impl ImageSource for EmptyImage { }
 
 pub trait ImageSource {
-    fn draw(&self, _renderer: &mut Renderer, _position: Point, _size: Size) { }
+    fn draw(&self, _renderer: &mut dyn Renderer, _position: Point, _size: Size) { }
+
+    /// The natural size of this image in pixels, if it has one
+    fn size(&self) -> Option<Size> { None }
+}
+
+/// An image that stretches a region of a texture to fill the area it is drawn in.
+pub struct TextureRegion {
+    texture: Rc<Texture>,
+    region: Rect,
+}
+
+impl TextureRegion {
+    /// Creates a new image from the `region` of the `texture`, in pixels.
+    pub fn new(texture: Rc<Texture>, region: Rect) -> TextureRegion {
+        TextureRegion { texture, region }
+    }
+
+    /// Creates a new image covering the whole of the `texture`.
+    pub fn from_texture(texture: Rc<Texture>) -> TextureRegion {
+        let region = Rect { x: 0, y: 0, width: texture.width(), height: texture.height() };
+        TextureRegion { texture, region }
+    }
+}
+
+impl ImageSource for TextureRegion {
+    fn draw(&self, renderer: &mut dyn Renderer, position: Point, size: Size) {
+        let uv = UvRect::from_pixels(self.region, self.texture.width(), self.texture.height());
+        renderer.draw_quad(&self.texture, Rect::new(position, size), uv, Color::default());
+    }
+
+    fn size(&self) -> Option<Size> { Some(self.region.size()) }
+}
+
+/// An image that stretches a region of a texture to any size without
+/// distorting its edges.  The `insets` split the region into a 3x3 grid - the
+/// corners are drawn at their original size, the edges are stretched along one
+/// axis, and the center is stretched along both.
+pub struct NineSlice {
+    texture: Rc<Texture>,
+    region: Rect,
+    insets: Border,
+}
+
+impl NineSlice {
+    /// Creates a new nine slice image from the `region` of the `texture`, in
+    /// pixels.
+    pub fn new(texture: Rc<Texture>, region: Rect, insets: Border) -> NineSlice {
+        NineSlice { texture, region, insets }
+    }
+}
+
+impl ImageSource for NineSlice {
+    fn draw(&self, renderer: &mut dyn Renderer, position: Point, size: Size) {
+        let src = self.region;
+        let insets = self.insets;
+
+        let src_x = slices(src.x, src.width, insets.left, insets.right);
+        let src_y = slices(src.y, src.height, insets.top, insets.bottom);
+        let dst_x = slices(position.x, size.width, insets.left, insets.right);
+        let dst_y = slices(position.y, size.height, insets.top, insets.bottom);
+
+        let (tex_width, tex_height) = (self.texture.width(), self.texture.height());
+        for row in 0..3 {
+            for col in 0..3 {
+                let (sx, sw) = src_x[col];
+                let (sy, sh) = src_y[row];
+                let (dx, dw) = dst_x[col];
+                let (dy, dh) = dst_y[row];
+                if sw == 0 || sh == 0 || dw == 0 || dh == 0 { continue; }
+
+                let region = Rect { x: sx, y: sy, width: sw, height: sh };
+                let uv = UvRect::from_pixels(region, tex_width, tex_height);
+                let rect = Rect { x: dx, y: dy, width: dw, height: dh };
+                renderer.draw_quad(&self.texture, rect, uv, Color::default());
+            }
+        }
+    }
+
+    fn size(&self) -> Option<Size> { Some(self.region.size()) }
+}
+
+// Splits the span of `length` starting at `start` into (start, length) pairs for
+// the leading inset, the middle, and the trailing inset.  If the span is too small
+// to hold both insets, they are scaled down to fit.
+fn slices(start: i32, length: u32, lead: u32, trail: u32) -> [(i32, u32); 3] {
+    let (lead, trail) = if length < lead + trail {
+        let lead = (lead as u64 * length as u64 / (lead + trail) as u64) as u32;
+        (lead, length - lead)
+    } else {
+        (lead, trail)
+    };
+
+    let middle = length - lead - trail;
+    [
+        (start, lead),
+        (start + lead as i32, middle),
+        (start + (lead + middle) as i32, trail),
+    ]
+}
+
+/// The set of named images that themes may refer to with their `background`
+/// and `foreground` fields.
+#[derive(Default)]
+pub struct ImageRegistry {
+    images: HashMap<String, Rc<dyn ImageSource>>,
+    textures: HashMap<String, Rc<Texture>>,
+
+    // missing image IDs that have already been warned about
+    warned: RefCell<HashSet<String>>,
+}
+
+impl ImageRegistry {
+    pub fn new() -> ImageRegistry {
+        ImageRegistry::default()
+    }
+
+    /// Adds the `source` with the specified `id`, replacing any existing image
+    /// with that ID.
+    pub fn insert<S: Into<String>>(&mut self, id: S, source: Rc<dyn ImageSource>) {
+        self.images.insert(id.into(), source);
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.images.contains_key(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Rc<dyn ImageSource>> {
+        self.images.get(id).cloned()
+    }
+
+    /// Adds the `texture` with the specified `id`, for use by image definitions.
+    pub fn insert_texture<S: Into<String>>(&mut self, id: S, texture: Rc<Texture>) {
+        self.textures.insert(id.into(), texture);
+    }
+
+    pub fn texture(&self, id: &str) -> Option<Rc<Texture>> {
+        self.textures.get(id).cloned()
+    }
+
+    /// Adds all of the textures and image definitions from the `themes`.
+    pub fn insert_theme_images(&mut self, themes: &ThemeSet) -> Result<(), Error> {
+        for (id, texture) in themes.textures() {
+            self.insert_texture(id.to_string(), Rc::clone(texture));
+        }
+        self.insert_definitions(themes.image_definitions())
+    }
+
+    /// Creates an image for each of the `definitions`, typically from
+    /// `ThemeSet::image_definitions`.  The textures they refer to must already
+    /// have been added with `insert_texture`.  Definitions with a missing texture
+    /// are skipped and reported in the returned error.
+    pub fn insert_definitions(&mut self, definitions: &HashMap<String, ImageDefinition>)
+        -> Result<(), Error> {
+        let mut missing = Vec::new();
+        for (id, definition) in definitions {
+            let texture = match self.texture(&definition.texture) {
+                None => {
+                    missing.push(format!("Texture '{}' for image '{}' is not in the \
+                                         image registry", definition.texture, id));
+                    continue;
+                },
+                Some(texture) => texture,
+            };
+
+            let region = definition.region.unwrap_or(Rect {
+                x: 0, y: 0, width: texture.width(), height: texture.height()
+            });
+
+            let source: Rc<dyn ImageSource> = match definition.insets {
+                None => Rc::new(TextureRegion::new(texture, region)),
+                Some(insets) => Rc::new(NineSlice::new(texture, region, insets)),
+            };
+            self.insert(id.to_string(), source);
+        }
+
+        if missing.is_empty() { return Ok(()); }
+
+        missing.sort();
+        Err(Error::new(ErrorKind::InvalidInput, missing.join(".  ")))
+    }
+
+    /// Creates an `Image` for the specified `id`, or an empty image if `id` is
+    /// `None` or is not in this registry.  A missing `id` is only warned about
+    /// the first time it is requested.
+    pub fn image(&self, id: Option<&str>) -> Image {
+        let id = match id {
+            None => return Image::default(),
+            Some(id) => id,
+        };
+
+        match self.get(id) {
+            None => {
+                if self.warned.borrow_mut().insert(id.to_string()) {
+                    warn!("Image '{}' is not in the image registry", id);
+                }
+                Image::default()
+            },
+            Some(source) => Image::new(source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::TextRun;
+
+    type Quad = ([i32; 4], [i32; 4]);
+
+    // records each quad as its destination rect and source region in pixels
+    #[derive(Default)]
+    struct QuadRecorder {
+        quads: Vec<Quad>,
+    }
+
+    impl Renderer for QuadRecorder {
+        fn fill_rect(&mut self, _rect: Rect, _color: Color) {}
+
+        fn draw_quad(&mut self, texture: &Texture, rect: Rect, uv: UvRect, _color: Color) {
+            let (w, h) = (texture.width() as f32, texture.height() as f32);
+            let region = [(uv.x * w) as i32, (uv.y * h) as i32,
+                          (uv.width * w) as i32, (uv.height * h) as i32];
+            self.quads.push(([rect.x, rect.y, rect.width as i32, rect.height as i32], region));
+        }
+
+        fn draw_text(&mut self, _run: &TextRun) {}
+
+        fn push_clip(&mut self, _rect: Rect) {}
+
+        fn pop_clip(&mut self) {}
+    }
+
+    fn draw_nine_slice(width: u32, height: u32) -> Vec<Quad> {
+        let texture = Rc::new(Texture::new(8, 8, vec![255; 8 * 8 * 4]));
+        let region = Rect { x: 1, y: 1, width: 6, height: 6 };
+        let insets = Border { top: 2, bottom: 2, left: 2, right: 2 };
+        let image = NineSlice::new(texture, region, insets);
+
+        let mut renderer = QuadRecorder::default();
+        image.draw(&mut renderer, Point { x: 10, y: 20 }, Size { width, height });
+        renderer.quads
+    }
+
+    fn definition(texture: &str) -> ImageDefinition {
+        ImageDefinition { texture: texture.to_string(), region: None, insets: None }
+    }
+
+    #[test]
+    fn insert_definitions_skips_missing_textures() {
+        let mut registry = ImageRegistry::new();
+        registry.insert_texture("texture", Rc::new(Texture::new(1, 1, vec![255; 4])));
+
+        let mut definitions = HashMap::new();
+        definitions.insert("found".to_string(), definition("texture"));
+        definitions.insert("missing".to_string(), definition("other"));
+
+        let err = registry.insert_definitions(&definitions).unwrap_err();
+        assert_eq!(err.to_string(),
+                   "Texture 'other' for image 'missing' is not in the image registry");
+        assert!(registry.contains("found"));
+        assert!(!registry.contains("missing"));
+    }
+
+    #[test]
+    fn missing_image_is_warned_about_once() {
+        let registry = ImageRegistry::new();
+        registry.image(Some("missing"));
+        registry.image(Some("missing"));
+        assert_eq!(registry.warned.borrow().len(), 1);
+    }
+
+    #[test]
+    fn nine_slice_stretches_edges_and_center() {
+        assert_eq!(draw_nine_slice(20, 10), vec![
+            ([10, 20, 2, 2], [1, 1, 2, 2]), ([12, 20, 16, 2], [3, 1, 2, 2]),
+            ([28, 20, 2, 2], [5, 1, 2, 2]),
+            ([10, 22, 2, 6], [1, 3, 2, 2]), ([12, 22, 16, 6], [3, 3, 2, 2]),
+            ([28, 22, 2, 6], [5, 3, 2, 2]),
+            ([10, 28, 2, 2], [1, 5, 2, 2]), ([12, 28, 16, 2], [3, 5, 2, 2]),
+            ([28, 28, 2, 2], [5, 5, 2, 2]),
+        ]);
+    }
+
+    #[test]
+    fn nine_slice_scales_corners_down_when_too_small() {
+        // no room for the edges or center, and the corners share the space
+        assert_eq!(draw_nine_slice(3, 2), vec![
+            ([10, 20, 1, 1], [1, 1, 2, 2]), ([11, 20, 2, 1], [5, 1, 2, 2]),
+            ([10, 21, 1, 1], [1, 5, 2, 2]), ([11, 21, 2, 1], [5, 5, 2, 2]),
+        ]);
+        assert_eq!(draw_nine_slice(4, 1), vec![
+            ([10, 20, 2, 1], [1, 5, 2, 2]), ([12, 20, 2, 1], [5, 5, 2, 2]),
+        ]);
+        assert!(draw_nine_slice(0, 0).is_empty());
+    }
 }
@@ -31,6 +31,13 @@ pub enum EventKind {
     MouseMoved { delta_x: f32, delta_y: f32 },
     MousePressed { button: MouseButton },
     MouseReleased { button: MouseButton},
+    MouseScrolled { delta_x: f32, delta_y: f32, unit: ScrollUnit },
+    KeyPressed { key: Key, modifiers: Modifiers },
+    KeyReleased { key: Key, modifiers: Modifiers },
+
+    /// Text entered by the user, after keyboard layout and input method
+    /// processing.  This is sent in addition to the key events.
+    TextInput { text: String },
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -39,3 +46,132 @@ pub enum MouseButton {
     Right,
     Middle,
 }
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum EventPhase {
+    /// The event is being sent to the ancestors of the target, from the root down
+    Capture,
+
+    /// The event is being sent to the target widget
+    Target,
+
+    /// The event is being sent to the ancestors of the target, back up to the root
+    Bubble,
+}
+
+/// The state of an event as it is propagated through the `WidgetTree`
+#[derive(Debug, Clone)]
+pub struct EventContext {
+    pub(crate) phase: EventPhase,
+    pub(crate) target: usize,
+    pub(crate) current: usize,
+    propagation_stopped: bool,
+    default_prevented: bool,
+}
+
+impl EventContext {
+    pub(crate) fn new(target: usize) -> EventContext {
+        EventContext {
+            phase: EventPhase::Capture,
+            target,
+            current: target,
+            propagation_stopped: false,
+            default_prevented: false,
+        }
+    }
+
+    pub fn phase(&self) -> EventPhase { self.phase }
+
+    /// The index of the widget the event is being sent to
+    pub fn target(&self) -> usize { self.target }
+
+    /// The index of the widget whose callback is currently being fired
+    pub fn current(&self) -> usize { self.current }
+
+    /// Prevents the event from being sent to any further widgets
+    pub fn stop_propagation(&mut self) {
+        self.propagation_stopped = true;
+    }
+
+    pub fn is_propagation_stopped(&self) -> bool { self.propagation_stopped }
+
+    /// Prevents the tree from taking its default action for the event, such as
+    /// moving the focus when Tab is pressed
+    pub fn prevent_default(&mut self) {
+        self.default_prevented = true;
+    }
+
+    pub fn is_default_prevented(&self) -> bool { self.default_prevented }
+}
+
+/// The units of a `MouseScrolled` delta.  Most mouse wheels scroll in lines,
+/// while touchpads typically scroll in pixels.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ScrollUnit {
+    Lines,
+    Pixels,
+}
+
+/// The state of the modifier keys when a key event occurred
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl Modifiers {
+    /// Returns true if no modifier keys are held
+    pub fn is_empty(&self) -> bool {
+        !self.shift && !self.control && !self.alt && !self.logo
+    }
+}
+
+/// A physical key, named by its position on a US keyboard layout
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Key {
+    A, B, C, D, E, F, G, H, I, J, K, L, M,
+    N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+
+    Escape,
+    Enter,
+    Tab,
+    Backspace,
+    Space,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Left,
+    Right,
+    Up,
+    Down,
+
+    Minus,
+    Equals,
+    LeftBracket,
+    RightBracket,
+    Backslash,
+    Semicolon,
+    Apostrophe,
+    Grave,
+    Comma,
+    Period,
+    Slash,
+
+    LeftShift,
+    RightShift,
+    LeftControl,
+    RightControl,
+    LeftAlt,
+    RightAlt,
+    LeftLogo,
+    RightLogo,
+
+    Unknown,
+}
@@ -16,25 +16,46 @@
 
 use std::any::Any;
 
-use crate::widget::{Widget, WidgetState};
-use crate::widget::Renderer;
+use crate::font::FontRegistry;
+use crate::image::ImageRegistry;
+use crate::render::Renderer;
+use crate::rich_text::{parse_markup, RichText, RichTextLayout, Span};
+use crate::widget::{Size, Widget, WidgetState};
 
 widget! {
     #[derive(Default)]
     pub struct Label {
-        text: String
+        text: Option<String>,
+        spans: Vec<Span>,
+        rich_text: RichText,
+        text_layout: RichTextLayout
     }
 
-    fn draw(&self, renderer: &mut Renderer) {
+    fn draw(&self, renderer: &mut dyn Renderer) {
         self.state().draw(renderer);
-        println!("{}", self.text);
+        self.text_layout.draw(renderer);
+    }
+
+    fn layout(&mut self) {
+        self.text_layout = self.rich_text.layout(self.state.text_params(), self.state.inner());
+    }
+
+    fn resolve_resources(&mut self, fonts: &FontRegistry, images: &ImageRegistry) {
+        self.spans = parse_markup(self.text());
+        self.rich_text = RichText::new(&self.spans, self.state.text_params(), fonts, images);
+    }
+
+    fn content_size(&self) -> Size {
+        self.rich_text.measure(self.state().text_params())
     }
 }
 
 impl Label {
+    /// Creates a label showing `text`, which may contain markup as described in
+    /// the `rich_text` module.
     pub fn new(text: String) -> Label {
-        let mut label= Label {
-            text,
+        let mut label = Label {
+            text: Some(text),
             ..Default::default()
         };
 
@@ -43,7 +64,78 @@ impl Label {
         label
     }
 
+    /// Returns the text shown by this label.  Text set in code with `new` or
+    /// `set_text` takes precedence over the theme `text`, which is only shown
+    /// when no text has been set, and is available once the label has been laid out.
     pub fn text(&self) -> &str {
-        &self.text
+        match &self.text {
+            Some(text) => text,
+            None => self.state.theme_text().unwrap_or_default(),
+        }
+    }
+
+    pub fn set_text<S: Into<String>>(&mut self, text: S) {
+        self.text = Some(text.into());
+        self.state.invalidate_layout();
+    }
+
+    /// Removes any text set in code, so the theme `text` is shown instead
+    pub fn reset_text(&mut self) {
+        self.text = None;
+        self.state.invalidate_layout();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme_builder::ThemeBuilderSet;
+    use crate::widget::EmptyWidget;
+    use crate::widget_tree::WidgetTree;
+
+    const THEMES: &str = "
+themes:
+  root:
+    children:
+      greeting:
+        kind: Label
+        text: hello
+  titled:
+    text: theme title
+";
+
+    fn tree() -> WidgetTree {
+        let builder: ThemeBuilderSet = serde_yaml::from_str(THEMES).unwrap();
+        let mut root = EmptyWidget::new();
+        root.set_theme("root");
+        WidgetTree::new(root, builder.create_theme_set().unwrap())
+    }
+
+    #[test]
+    fn theme_label_shows_theme_text() {
+        let mut tree = tree();
+        tree.layout();
+        assert_eq!(tree.widget::<Label>(1).text(), "hello");
+    }
+
+    #[test]
+    fn text_set_in_code_overrides_theme_text() {
+        let mut tree = tree();
+        let mut label = Label::new("from code".to_string());
+        label.set_theme("titled");
+        // added after the theme label at index 1
+        tree.add_child(0, label);
+        let index = 2;
+
+        tree.layout();
+        assert_eq!(tree.widget::<Label>(index).text(), "from code");
+
+        tree.widget_mut::<Label>(1).set_text("changed");
+        tree.layout();
+        assert_eq!(tree.widget::<Label>(1).text(), "changed");
+
+        tree.widget_mut::<Label>(index).reset_text();
+        tree.layout();
+        assert_eq!(tree.widget::<Label>(index).text(), "theme title");
     }
 }
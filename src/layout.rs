@@ -0,0 +1,576 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+use std::collections::HashSet;
+
+use log::warn;
+
+use crate::expression::Variables;
+use crate::theme::{GridTracks, LayoutKind, PositionRelative, SizeRelative, Theme};
+use crate::widget::{Point, Rect, Size};
+
+/// Computes the size of a widget with the specified `theme` that can be known
+/// without reference to its parent, given the already measured sizes of its
+/// `children` and the size of the widget's own `content`.  Parent relative and
+/// custom sizes only include the fixed theme component here, and are resolved
+/// in `place`.
+pub(crate) fn measure(theme: &Theme, children: &[Size], content: Size) -> Size {
+    let border = theme.border;
+    let widths: Vec<u32> = children.iter().map(|size| size.width).collect();
+    let heights: Vec<u32> = children.iter().map(|size| size.height).collect();
+
+    let (max, sum) = content_extent(theme, &widths, false);
+    let width = measure_axis(&theme.relative.width, theme.size.width, border.left + border.right,
+                             (max.max(content.width), sum + content.width));
+    let (max, sum) = content_extent(theme, &heights, true);
+    let height = measure_axis(&theme.relative.height, theme.size.height,
+                              border.top + border.bottom,
+                              (max.max(content.height), sum + content.height));
+
+    constrain(theme, Size { width, height })
+}
+
+/// Returns true if the size of a widget with the specified `theme` is computed
+/// from the sizes of its children.
+pub(crate) fn depends_on_children(theme: &Theme) -> bool {
+    let by_children = |relative: &SizeRelative| {
+        matches!(relative, SizeRelative::ChildMax | SizeRelative::ChildSum)
+    };
+    by_children(&theme.relative.width) || by_children(&theme.relative.height)
+}
+
+fn constrain(theme: &Theme, size: Size) -> Size {
+    Size {
+        width: theme.size_limits.clamp_width(size.width),
+        height: theme.size_limits.clamp_height(size.height),
+    }
+}
+
+// Returns the (ChildMax, ChildSum) extents of the children along one axis,
+// accounting for the layout of the parent.
+fn content_extent(theme: &Theme, children: &[u32], vertical: bool) -> (u32, u32) {
+    let max = children.iter().cloned().max().unwrap_or(0);
+    let sum = children.iter().sum::<u32>();
+    let gaps = children.len().saturating_sub(1) as u32;
+
+    match theme.layout {
+        LayoutKind::BoxVertical if vertical => (max, sum + gaps * box_spacing(theme)),
+        LayoutKind::BoxHorizontal if !vertical => (max, sum + gaps * box_spacing(theme)),
+        LayoutKind::Grid => {
+            let (tracks, spacing) = grid_tracks(theme, vertical);
+            let extent = grid_extent(tracks, spacing, max);
+            (extent, extent)
+        },
+        _ => (max, sum),
+    }
+}
+
+fn measure_axis(relative: &SizeRelative, fixed: u32, border: u32, content: (u32, u32)) -> u32 {
+    use crate::theme::SizeRelative::*;
+    match relative {
+        Zero | Parent | Custom(_) => fixed,
+        ChildMax => fixed + border + content.0,
+        ChildSum => fixed + border + content.1,
+    }
+}
+
+/// Computes the bounds of each of the `children` of a widget with the specified
+/// `theme`, within that widget's `inner` area.  Each child is given as its theme
+/// and measured size.  `Normal` layouts place every child independently within
+/// the inner area, while box layouts stack the children along one axis, separated
+/// by the `layout_spacing` of the parent.  Grid layouts place each child within
+/// the cells given by its `grid_cell`.  Children without a column and row are
+/// placed in the next free cells, in row major order, that fit their spans.
+pub(crate) fn arrange(theme: &Theme, inner: Rect, children: &[(&Theme, Size)]) -> Vec<Rect> {
+    match theme.layout {
+        LayoutKind::BoxVertical => arrange_box(box_spacing(theme), inner, children, true),
+        LayoutKind::BoxHorizontal => arrange_box(box_spacing(theme), inner, children, false),
+        LayoutKind::Grid => arrange_grid(theme, inner, children),
+        LayoutKind::Normal => {
+            children.iter().map(|(child, measured)| place(child, *measured, inner)).collect()
+        }
+    }
+}
+
+// The gap between consecutive items in a box layout - the space after one item
+// plus the space before the next.
+fn box_spacing(theme: &Theme) -> u32 {
+    let spacing = theme.layout_spacing;
+    match theme.layout {
+        LayoutKind::BoxHorizontal => spacing.left + spacing.right,
+        _ => spacing.top + spacing.bottom,
+    }
+}
+
+fn arrange_box(spacing: u32, inner: Rect, children: &[(&Theme, Size)],
+               vertical: bool) -> Vec<Rect> {
+    // the cross axis is placed normally, the main axis is overridden
+    let mut rects: Vec<Rect> = children.iter()
+        .map(|(child, measured)| place(child, *measured, inner)).collect();
+
+    let available = if vertical { inner.height } else { inner.width };
+    let gaps = rects.len().saturating_sub(1) as u32 * spacing;
+    let mut lengths: Vec<u32> = rects.iter()
+        .map(|rect| if vertical { rect.height } else { rect.width }).collect();
+    flex(children, &mut lengths, available as i64 - gaps as i64, vertical);
+
+    let mut cursor = if vertical { inner.y } else { inner.x };
+    for (rect, length) in rects.iter_mut().zip(lengths) {
+        if vertical {
+            rect.y = cursor;
+            rect.height = length;
+        } else {
+            rect.x = cursor;
+            rect.width = length;
+        }
+        cursor += (length + spacing) as i32;
+    }
+    rects
+}
+
+// Grows or shrinks the main axis `lengths` of the children of a box according
+// to their flex weights, so that they fill the `available` space.  Children that
+// hit their size limits are frozen and the remaining space is shared out again
+// between the others.
+fn flex(children: &[(&Theme, Size)], lengths: &mut [u32], available: i64, vertical: bool) {
+    let mut frozen = vec![false; lengths.len()];
+
+    loop {
+        let free = available - lengths.iter().map(|l| *l as i64).sum::<i64>();
+        if free == 0 { return; }
+
+        let weights: Vec<f32> = children.iter().zip(lengths.iter()).zip(frozen.iter())
+            .map(|(((child, _), length), frozen)| {
+                if *frozen {
+                    0.0
+                } else if free > 0 {
+                    child.flex.grow.max(0.0)
+                } else {
+                    child.flex.shrink.max(0.0) * *length as f32
+                }
+            }).collect();
+
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 { return; }
+
+        let mut any_frozen = false;
+        let mut fractions = Vec::new();
+        for (i, (child, _)) in children.iter().enumerate() {
+            if weights[i] <= 0.0 { continue; }
+
+            let exact = (lengths[i] as f32 + free as f32 * weights[i] / total).max(0.0);
+            let target = exact as u32;
+            let limited = if vertical {
+                child.size_limits.clamp_height(target)
+            } else {
+                child.size_limits.clamp_width(target)
+            };
+
+            if limited != target {
+                frozen[i] = true;
+                any_frozen = true;
+            } else {
+                fractions.push((i, exact - target as f32));
+            }
+            lengths[i] = limited;
+        }
+
+        if !any_frozen {
+            // hand out the pixels lost to truncation, largest fractional part first
+            let remainder = available - lengths.iter().map(|l| *l as i64).sum::<i64>();
+            fractions.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+            for (i, _) in fractions.iter().take(remainder.max(0) as usize) {
+                lengths[*i] += 1;
+            }
+            return;
+        }
+    }
+}
+
+/// Computes the final bounds of a widget with the specified `theme` and
+/// `measured` size, placed within the `parent` rect.
+pub(crate) fn place(theme: &Theme, measured: Size, parent: Rect) -> Rect {
+    let mut vars = Variables {
+        parent_width: parent.width as f32,
+        parent_height: parent.height as f32,
+        width: measured.width as f32,
+        height: measured.height as f32,
+        percent_of: 0.0,
+    };
+
+    let width = place_size(&theme.relative.width, measured.width, theme.size.width,
+                           parent.width, vars);
+    let height = place_size(&theme.relative.height, measured.height, theme.size.height,
+                            parent.height, vars);
+    let Size { width, height } = constrain(theme, Size { width, height });
+
+    vars.width = width as f32;
+    vars.height = height as f32;
+    let x = place_position(&theme.relative.x, theme.position.x, parent.x, parent.width,
+                           width, vars);
+    let y = place_position(&theme.relative.y, theme.position.y, parent.y, parent.height,
+                           height, vars);
+
+    Rect::new(Point { x, y }, Size { width, height })
+}
+
+fn place_size(relative: &SizeRelative, measured: u32, fixed: u32, parent: u32,
+              vars: Variables) -> u32 {
+    match relative {
+        SizeRelative::Parent => parent + fixed,
+        SizeRelative::Custom(expr) => {
+            let vars = Variables { percent_of: parent as f32, ..vars };
+            expr.evaluate(&vars).round().max(0.0) as u32
+        },
+        _ => measured,
+    }
+}
+
+// Custom positions are an offset from the start of the parent area, and unlike
+// the other kinds do not add the theme `position` on top
+fn place_position(relative: &PositionRelative, offset: i32, parent_start: i32,
+                  parent_size: u32, size: u32, vars: Variables) -> i32 {
+    use crate::theme::PositionRelative::*;
+    let free = parent_size as i32 - size as i32;
+    match relative {
+        Zero => parent_start + offset,
+        Center => parent_start + free / 2 + offset,
+        Max => parent_start + free + offset,
+        Custom(expr) => {
+            let vars = Variables { percent_of: parent_size as f32, ..vars };
+            parent_start + expr.evaluate(&vars).round() as i32
+        },
+    }
+}
+
+fn grid_tracks(theme: &Theme, vertical: bool) -> (&GridTracks, u32) {
+    let spacing = theme.layout_spacing;
+    if vertical {
+        (&theme.grid.rows, spacing.top + spacing.bottom)
+    } else {
+        (&theme.grid.columns, spacing.left + spacing.right)
+    }
+}
+
+// The total size of the specified tracks, with equally sized tracks each
+// large enough to hold the `largest` child.
+fn grid_extent(tracks: &GridTracks, spacing: u32, largest: u32) -> u32 {
+    let gaps = tracks.len().saturating_sub(1) as u32 * spacing;
+    match tracks {
+        GridTracks::Count(count) => count * largest + gaps,
+        GridTracks::Sizes(sizes) => sizes.iter().sum::<u32>() + gaps,
+    }
+}
+
+// Computes the (offset, size) of each track within the available length.
+fn track_bounds(tracks: &GridTracks, spacing: u32, length: u32) -> Vec<(u32, u32)> {
+    let sizes: Vec<u32> = match tracks {
+        GridTracks::Count(0) => Vec::new(),
+        GridTracks::Count(count) => {
+            let gaps = (count - 1) * spacing;
+            let available = length.saturating_sub(gaps);
+            let base = available / count;
+            let remainder = available % count;
+            (0..*count).map(|i| if i < remainder { base + 1 } else { base }).collect()
+        },
+        GridTracks::Sizes(sizes) => sizes.clone(),
+    };
+
+    let mut offset = 0;
+    sizes.into_iter().map(|size| {
+        let bounds = (offset, size);
+        offset += size + spacing;
+        bounds
+    }).collect()
+}
+
+// Returns the (offset, size) covered by `span` tracks starting at `index`,
+// clamped to the tracks that exist.
+fn track_span(bounds: &[(u32, u32)], index: u32, span: u32) -> (u32, u32) {
+    if bounds.is_empty() { return (0, 0); }
+
+    let last_index = bounds.len() - 1;
+    let first = bounds[(index as usize).min(last_index)];
+    let last = bounds[(index as usize + span.max(1) as usize - 1).min(last_index)];
+    (first.0, last.0 + last.1 - first.0)
+}
+
+fn arrange_grid(theme: &Theme, inner: Rect, children: &[(&Theme, Size)]) -> Vec<Rect> {
+    let (columns, column_spacing) = grid_tracks(theme, false);
+    let (rows, row_spacing) = grid_tracks(theme, true);
+    let columns = track_bounds(columns, column_spacing, inner.width);
+    let rows = track_bounds(rows, row_spacing, inner.height);
+    let num_columns = columns.len().max(1) as u32;
+    let num_rows = rows.len().max(1) as u32;
+
+    // children with an explicit column and row claim their cells first, then the
+    // remaining children are placed in the free cells in declaration order
+    let mut cells: Vec<Option<GridArea>> = vec![None; children.len()];
+    let mut occupied = HashSet::new();
+    for (cell, (child, _)) in cells.iter_mut().zip(children) {
+        let grid_cell = child.grid_cell.unwrap_or_default();
+        if let (Some(column), Some(row)) = (grid_cell.column, grid_cell.row) {
+            let (column, column_span) = clamp_tracks(child, "column", column,
+                                                     grid_cell.column_span, num_columns);
+            let (row, row_span) = clamp_tracks(child, "row", row, grid_cell.row_span, num_rows);
+            let area = GridArea { column, row, column_span, row_span };
+            area.occupy(&mut occupied);
+            *cell = Some(area);
+        }
+    }
+
+    let mut next_cell = 0;
+    for (cell, (child, _)) in cells.iter_mut().zip(children) {
+        if cell.is_some() { continue; }
+
+        let grid_cell = child.grid_cell.unwrap_or_default();
+        let (column, column_span) = clamp_tracks(child, "column", grid_cell.column.unwrap_or(0),
+                                                 grid_cell.column_span, num_columns);
+        let column = grid_cell.column.map(|_| column);
+        let row_span = grid_cell.row_span.max(1);
+
+        let mut area = GridArea { column: 0, row: 0, column_span, row_span };
+        match (column, grid_cell.row) {
+            (None, None) => {
+                for index in next_cell.. {
+                    area.column = index % num_columns;
+                    area.row = index / num_columns;
+                    if area.column + column_span <= num_columns && area.is_free(&occupied) {
+                        next_cell = index + column_span;
+                        break;
+                    }
+                }
+            },
+            (Some(column), _) => {
+                area.column = column;
+                for row in 0.. {
+                    area.row = row;
+                    if area.is_free(&occupied) { break; }
+                }
+            },
+            (None, Some(row)) => {
+                area.row = row;
+                let free = (0..=num_columns - column_span).find(|column| {
+                    GridArea { column: *column, ..area }.is_free(&occupied)
+                });
+                match free {
+                    Some(column) => area.column = column,
+                    None => warn!("No free cells in row {} of the grid for '{}'", row, child.id),
+                }
+            },
+        }
+
+        let (row, row_span) = clamp_tracks(child, "row", area.row, area.row_span, num_rows);
+        area.row = row;
+        area.row_span = row_span;
+        area.occupy(&mut occupied);
+        *cell = Some(area);
+    }
+
+    cells.into_iter().zip(children).map(|(cell, (child, measured))| {
+        let cell = cell.unwrap();
+        let (x, width) = track_span(&columns, cell.column, cell.column_span);
+        let (y, height) = track_span(&rows, cell.row, cell.row_span);
+        let area = Rect { x: inner.x + x as i32, y: inner.y + y as i32, width, height };
+        place(child, *measured, area)
+    }).collect()
+}
+
+// The tracks covered by a child of a grid layout
+#[derive(Debug, Clone, Copy)]
+struct GridArea {
+    column: u32,
+    row: u32,
+    column_span: u32,
+    row_span: u32,
+}
+
+impl GridArea {
+    fn cells(&self) -> impl Iterator<Item=(u32, u32)> {
+        let (row, row_span) = (self.row, self.row_span);
+        (self.column..self.column + self.column_span)
+            .flat_map(move |column| (row..row + row_span).map(move |row| (column, row)))
+    }
+
+    fn is_free(&self, occupied: &HashSet<(u32, u32)>) -> bool {
+        self.cells().all(|cell| !occupied.contains(&cell))
+    }
+
+    fn occupy(&self, occupied: &mut HashSet<(u32, u32)>) {
+        occupied.extend(self.cells());
+    }
+}
+
+// Clamps a track `index` and `span` to the `count` tracks that exist, warning if
+// they do not fit.
+fn clamp_tracks(child: &Theme, axis: &str, index: u32, span: u32, count: u32) -> (u32, u32) {
+    let span = span.max(1);
+    if index + span <= count { return (index, span); }
+
+    warn!("Grid {} {} with span {} for '{}' is outside the {} {}s of the grid",
+          axis, index, span, child.id, count, axis);
+    let index = index.min(count - 1);
+    (index, span.min(count - index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::{Border, Flex, Grid, GridCell, Relative, SizeLimits};
+
+    fn grid(columns: u32, rows: u32) -> Theme {
+        Theme {
+            layout: LayoutKind::Grid,
+            grid: Grid { columns: GridTracks::Count(columns), rows: GridTracks::Count(rows) },
+            ..Default::default()
+        }
+    }
+
+    // a child filling the cells it is placed in
+    fn cell(column: Option<u32>, row: Option<u32>, column_span: u32, row_span: u32) -> Theme {
+        Theme {
+            grid_cell: Some(GridCell { column, row, column_span, row_span }),
+            relative: Relative {
+                width: SizeRelative::Parent,
+                height: SizeRelative::Parent,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn auto() -> Theme { cell(None, None, 1, 1) }
+
+    // returns the (x, y, width, height) of each child in a 30 x 30 area
+    fn arrange_children(parent: &Theme, children: &[Theme]) -> Vec<(i32, i32, u32, u32)> {
+        let inner = Rect { x: 0, y: 0, width: 30, height: 30 };
+        let children: Vec<_> = children.iter().map(|child| (child, Size::default())).collect();
+        arrange(parent, inner, &children).into_iter()
+            .map(|rect| (rect.x, rect.y, rect.width, rect.height))
+            .collect()
+    }
+
+    #[test]
+    fn grid_auto_placement_skips_explicit_cells() {
+        let children = [auto(), cell(Some(1), Some(0), 1, 1), auto(), cell(Some(0), Some(1), 2, 1),
+                        auto()];
+        assert_eq!(arrange_children(&grid(3, 3), &children), vec![
+            (0, 0, 10, 10), (10, 0, 10, 10), (20, 0, 10, 10), (0, 10, 20, 10), (20, 10, 10, 10),
+        ]);
+    }
+
+    #[test]
+    fn grid_auto_placement_fits_spans() {
+        let children = [cell(None, None, 2, 1), cell(None, None, 2, 2), auto(), auto()];
+        assert_eq!(arrange_children(&grid(3, 3), &children), vec![
+            (0, 0, 20, 10), (0, 10, 20, 20), (20, 10, 10, 10), (20, 20, 10, 10),
+        ]);
+    }
+
+    #[test]
+    fn grid_auto_placement_in_fixed_column_or_row() {
+        let children = [cell(Some(1), Some(0), 1, 1), cell(Some(1), None, 1, 1),
+                        cell(None, Some(0), 1, 1)];
+        assert_eq!(arrange_children(&grid(3, 3), &children), vec![
+            (10, 0, 10, 10), (10, 10, 10, 10), (0, 0, 10, 10),
+        ]);
+    }
+
+    #[test]
+    fn grid_out_of_range_cells_are_clamped() {
+        let children = [cell(Some(5), Some(1), 1, 1), cell(Some(1), Some(2), 4, 3)];
+        assert_eq!(arrange_children(&grid(3, 3), &children), vec![
+            (20, 10, 10, 10), (10, 20, 20, 10),
+        ]);
+    }
+
+    // a child of a horizontal box with a fixed width and flex weights
+    fn item(width: u32, grow: f32, shrink: f32, size_limits: SizeLimits) -> Theme {
+        Theme {
+            size: Size { width, height: 10 },
+            flex: Flex { grow, shrink },
+            size_limits,
+            ..Default::default()
+        }
+    }
+
+    // returns the (x, width) of each child of a horizontal box `width` wide
+    fn arrange_row(parent: &Theme, width: u32, children: &[Theme]) -> Vec<(i32, u32)> {
+        let inner = Rect { x: 0, y: 0, width, height: 10 };
+        let children: Vec<_> = children.iter()
+            .map(|child| (child, Size { width: child.size.width, height: 10 })).collect();
+        arrange(parent, inner, &children).into_iter().map(|rect| (rect.x, rect.width)).collect()
+    }
+
+    fn row(spacing: u32) -> Theme {
+        Theme {
+            layout: LayoutKind::BoxHorizontal,
+            layout_spacing: Border { left: spacing, right: spacing, ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn flex_toolbar_spacer_fills_remaining_space() {
+        let button = || item(20, 0.0, 0.0, SizeLimits::default());
+        let spacer = item(0, 1.0, 0.0, SizeLimits::default());
+        let children = [button(), button(), spacer, button()];
+
+        assert_eq!(arrange_row(&row(0), 100, &children),
+                   vec![(0, 20), (20, 20), (40, 40), (80, 20)]);
+        assert_eq!(arrange_row(&row(1), 100, &children),
+                   vec![(0, 20), (22, 20), (44, 34), (80, 20)]);
+
+        // the spacer collapses rather than the buttons shrinking
+        assert_eq!(arrange_row(&row(0), 50, &children),
+                   vec![(0, 20), (20, 20), (40, 0), (40, 20)]);
+    }
+
+    #[test]
+    fn flex_grow_fills_space_exactly() {
+        let grow = || item(0, 1.0, 0.0, SizeLimits::default());
+
+        assert_eq!(arrange_row(&row(0), 100, &[grow(), grow(), grow()]),
+                   vec![(0, 34), (34, 33), (67, 33)]);
+        assert_eq!(arrange_row(&row(0), 101, &[grow(), grow(), grow()]),
+                   vec![(0, 34), (34, 34), (68, 33)]);
+    }
+
+    #[test]
+    fn flex_grow_freezes_at_max_size() {
+        let limits = SizeLimits { max_width: Some(10), ..Default::default() };
+        let children = [item(0, 1.0, 0.0, limits), item(0, 1.0, 0.0, SizeLimits::default()),
+                        item(0, 1.0, 0.0, SizeLimits::default())];
+
+        assert_eq!(arrange_row(&row(0), 90, &children), vec![(0, 10), (10, 40), (50, 40)]);
+    }
+
+    #[test]
+    fn flex_shrink_freezes_at_min_size() {
+        let limits = SizeLimits { min_width: 50, ..Default::default() };
+        let children = [item(60, 0.0, 1.0, limits), item(60, 0.0, 1.0, SizeLimits::default())];
+
+        assert_eq!(arrange_row(&row(0), 80, &children), vec![(0, 50), (50, 30)]);
+    }
+
+    #[test]
+    fn flex_shrink_is_weighted_by_size() {
+        let children = [item(30, 0.0, 1.0, SizeLimits::default()),
+                        item(90, 0.0, 1.0, SizeLimits::default())];
+
+        assert_eq!(arrange_row(&row(0), 80, &children), vec![(0, 20), (20, 60)]);
+    }
+}
@@ -31,9 +31,9 @@ macro_rules! widget {
 
             fn kind(&self) -> &'static str { stringify!($name) }
 
-            fn as_any(&self) -> &Any { self }
+            fn as_any(&self) -> &dyn Any { self }
 
-            fn as_any_mut(&mut self) -> &mut Any { self }
+            fn as_any_mut(&mut self) -> &mut dyn Any { self }
 
             $($fn_data)*
         }
@@ -43,17 +43,29 @@ macro_rules! widget {
                 let id = id.into();
                 self.state_mut().theme_partial_id = id.clone();
                 self.state_mut().theme_full_id = id;
+                self.state_mut().invalidate_layout();
             }
         }
     }
 }
 
 pub mod widget;
+pub mod bitmap_font;
 pub mod button;
 pub mod color;
+pub mod expression;
+pub mod font;
 pub mod image;
 pub mod input;
 pub mod label;
+pub mod layout;
+pub mod png_image;
+pub mod render;
+pub mod rich_text;
+pub mod snapshot;
+pub mod software_renderer;
+pub mod text_layout;
 pub mod theme;
 pub mod theme_builder;
+pub mod truetype_font;
 pub mod widget_tree;
@@ -0,0 +1,117 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind};
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::image::TextureRegion;
+use crate::render::Texture;
+
+/// Decodes the PNG file at `path` into an RGBA texture.  Grayscale, RGB and
+/// paletted images are expanded to RGBA.
+pub fn load_texture<P: AsRef<Path>>(path: P) -> Result<Texture, Error> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| png_error(path, err))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().map_err(|err| png_error(path, err))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|err| png_error(path, err))?;
+    buf.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf.chunks(3).flat_map(|p| vec![p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => {
+            buf.chunks(2).flat_map(|p| vec![p[0], p[0], p[0], p[1]]).collect()
+        },
+        png::ColorType::Grayscale => buf.iter().flat_map(|g| vec![*g, *g, *g, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(png_error(path, "Unable to expand indexed color"));
+        }
+    };
+
+    Ok(Texture::new(info.width, info.height, pixels))
+}
+
+/// Decodes the PNG file at `path` as in `load_texture`, returning an image
+/// that draws the entire texture.
+pub fn load_image<P: AsRef<Path>>(path: P) -> Result<TextureRegion, Error> {
+    let texture = load_texture(path)?;
+    Ok(TextureRegion::from_texture(Rc::new(texture)))
+}
+
+/// Encodes the RGBA `pixels` as a PNG file at `path`.
+pub fn write_png<P: AsRef<Path>>(path: P, width: u32, height: u32,
+                                 pixels: &[u8]) -> Result<(), Error> {
+    let path = path.as_ref();
+    let file = File::create(path).map_err(|err| png_error(path, err))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|err| png_error(path, err))?;
+    writer.write_image_data(pixels).map_err(|err| png_error(path, err))
+}
+
+/// Prefixes `err` with the path of the file, keeping the kind of any I/O error
+fn png_error<E: PngErrorKind + fmt::Display>(path: &Path, err: E) -> Error {
+    Error::new(err.kind(), format!("{}: {}", path.display(), err))
+}
+
+trait PngErrorKind {
+    fn kind(&self) -> ErrorKind { ErrorKind::InvalidData }
+}
+
+impl PngErrorKind for Error {
+    fn kind(&self) -> ErrorKind { Error::kind(self) }
+}
+
+impl PngErrorKind for png::DecodingError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            png::DecodingError::IoError(err) => err.kind(),
+            _ => ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl PngErrorKind for png::EncodingError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            png::EncodingError::IoError(err) => err.kind(),
+            _ => ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl PngErrorKind for &str { }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_keeps_not_found_kind() {
+        let err = load_texture("does/not/exist.png").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(err.to_string().contains("exist.png"));
+    }
+}
@@ -0,0 +1,191 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::color::Color;
+use crate::font::Font;
+use crate::theme::TextParams;
+use crate::widget::{Point, Rect};
+
+/// A drawing backend.  Widgets and images describe what they look like by
+/// calling these methods, and the backend is responsible for turning the
+/// primitives into pixels.  All coordinates are in the same pixel space as
+/// widget positions.
+pub trait Renderer {
+    /// Fills the `rect` with a solid `color`
+    fn fill_rect(&mut self, rect: Rect, color: Color);
+
+    /// Draws a `thickness` pixel wide outline just inside the `rect`
+    fn outline_rect(&mut self, rect: Rect, color: Color, thickness: u32) {
+        let t = thickness.min(rect.width / 2).min(rect.height / 2);
+        let inner_height = rect.height - 2 * t;
+        self.fill_rect(Rect { height: t, ..rect }, color);
+        self.fill_rect(Rect { y: rect.y + (rect.height - t) as i32, height: t, ..rect }, color);
+        self.fill_rect(Rect { y: rect.y + t as i32, width: t, height: inner_height, ..rect }, color);
+        self.fill_rect(Rect { x: rect.x + (rect.width - t) as i32, y: rect.y + t as i32,
+                              width: t, height: inner_height }, color);
+    }
+
+    /// Draws the `uv` region of the `texture` stretched to fill `rect`, with
+    /// each texel multiplied by `color`
+    fn draw_quad(&mut self, texture: &Texture, rect: Rect, uv: UvRect, color: Color);
+
+    /// Draws a single run of text.  Backends without their own text rendering
+    /// can use `draw_glyphs`.
+    fn draw_text(&mut self, run: &TextRun);
+
+    /// Restricts all subsequent drawing to the intersection of `rect` and the
+    /// current clip area, until the matching `pop_clip`
+    fn push_clip(&mut self, rect: Rect);
+
+    /// Restores the clip area in effect before the last `push_clip`
+    fn pop_clip(&mut self);
+}
+
+/// A region of a texture in normalized coordinates, where `0.0` to `1.0` covers
+/// the full width or height of the texture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for UvRect {
+    fn default() -> Self {
+        UvRect { x: 0.0, y: 0.0, width: 1.0, height: 1.0 }
+    }
+}
+
+impl UvRect {
+    /// Creates the UV rect covering the `region` of a texture with the specified
+    /// pixel dimensions.
+    pub fn from_pixels(region: Rect, texture_width: u32, texture_height: u32) -> UvRect {
+        let tw = texture_width.max(1) as f32;
+        let th = texture_height.max(1) as f32;
+        UvRect {
+            x: region.x as f32 / tw,
+            y: region.y as f32 / th,
+            width: region.width as f32 / tw,
+            height: region.height as f32 / th,
+        }
+    }
+}
+
+/// A single line of text with uniform style, positioned with its top left
+/// corner at `position`.
+#[derive(Clone)]
+pub struct TextRun<'a> {
+    pub text: &'a str,
+    pub position: Point,
+    pub params: &'a TextParams,
+    pub font: &'a dyn Font,
+}
+
+/// Draws the `run` by drawing each glyph of its font, with lines separated by `\n`.
+pub fn draw_glyphs(renderer: &mut dyn Renderer, run: &TextRun) {
+    let params = run.params;
+    let line_height = run.font.line_height() * params.scale;
+    let mut y = run.position.y as f32;
+    for line in run.text.split('\n') {
+        run.font.draw_line(renderer, line, run.position.x as f32, y, params.scale, params.color);
+        y += line_height;
+    }
+}
+
+static NEXT_TEXTURE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// RGBA pixel data, 8 bits per channel, in row major order.  Each texture has
+/// a unique `id` which backends may use to cache any uploaded copy.  Textures
+/// that are modified after creation, such as glyph atlases, keep their `id` and
+/// increment their `version`.  A copy of the previous version only needs the
+/// `changed_region` updated, while older copies need the whole texture.
+pub struct Texture {
+    id: usize,
+    version: u32,
+    changed: Rect,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl fmt::Debug for Texture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Texture {{ id: {}, version: {}, width: {}, height: {} }}", self.id, self.version,
+               self.width, self.height)
+    }
+}
+
+impl Texture {
+    /// Creates a new texture.  Panics if `pixels` is not exactly
+    /// `width * height * 4` bytes long.
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Texture {
+        assert_eq!(pixels.len(), width as usize * height as usize * 4);
+        Texture {
+            id: NEXT_TEXTURE_ID.fetch_add(1, Ordering::Relaxed),
+            version: 0,
+            changed: Rect { x: 0, y: 0, width, height },
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn id(&self) -> usize { self.id }
+
+    pub fn version(&self) -> u32 { self.version }
+
+    /// The area that differs from the previous version
+    pub fn changed_region(&self) -> Rect { self.changed }
+
+    /// Replaces the contents of this texture with `pixels` of a new size,
+    /// starting a new version.  Panics if `pixels` is the wrong length.
+    pub(crate) fn replace(&mut self, width: u32, height: u32, pixels: Vec<u8>) {
+        assert_eq!(pixels.len(), width as usize * height as usize * 4);
+        self.width = width;
+        self.height = height;
+        self.pixels = pixels;
+        self.mark_changed(Rect { x: 0, y: 0, width, height });
+    }
+
+    /// Gives access to the pixels for modification.  `mark_changed` must be
+    /// called afterwards so backends pick up the changes.
+    pub(crate) fn pixels_mut(&mut self) -> &mut [u8] { &mut self.pixels }
+
+    /// Starts a new version of this texture, with `region` modified.
+    pub(crate) fn mark_changed(&mut self, region: Rect) {
+        self.version = self.version.wrapping_add(1);
+        self.changed = region;
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+
+    pub fn height(&self) -> u32 { self.height }
+
+    pub fn pixels(&self) -> &[u8] { &self.pixels }
+
+    /// Returns the RGBA value of the pixel at the specified coordinates, which
+    /// are clamped to the texture bounds.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let x = x.min(self.width.saturating_sub(1));
+        let y = y.min(self.height.saturating_sub(1));
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+}
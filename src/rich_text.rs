@@ -0,0 +1,721 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+//! Inline markup for styled text.  The supported tags are:
+//!
+//! - `[color=f00]text[/color]` draws text in the specified color
+//! - `[b]text[/b]` and `[i]text[/i]` draw text in the bold or italic variant of
+//!   the current font.  For a font `sans`, these are the fonts named `sans-bold`,
+//!   `sans-italic` and `sans-bold-italic` in the `FontRegistry`, falling back to
+//!   `sans` if the variant is not present.
+//! - `[font=id]text[/font]` draws text with a different font
+//! - `[icon=id]` draws an image from the `ImageRegistry` inline with the text
+//!
+//! `[[` is an escaped `[`.  Tags that can't be parsed are drawn as plain text,
+//! while closing tags that don't match an open tag are ignored.
+
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::color::Color;
+use crate::font::{EmptyFont, Font, FontRegistry};
+use crate::image::{Image, ImageRegistry};
+use crate::render::{Renderer, TextRun};
+use crate::text_layout::ELLIPSIS;
+use crate::theme::{HorizontalAlignment, TextParams, VerticalAlignment};
+use crate::widget::{Point, Rect, Size};
+
+#[derive(Debug, Clone, Default)]
+pub struct SpanStyle {
+    /// The text color, or the `TextParams` color if `None`
+    pub color: Option<Color>,
+
+    /// The font ID, or the `TextParams` font if `None`
+    pub font: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum SpanContent {
+    Text(String),
+
+    /// The ID of an image in the `ImageRegistry`
+    Icon(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub content: SpanContent,
+    pub style: SpanStyle,
+}
+
+enum Tag {
+    Open(&'static str, SpanStyle),
+    Close(String),
+    Icon(String),
+}
+
+fn parse_tag(tag: &str, style: &SpanStyle) -> Option<Tag> {
+    if let Some(name) = tag.strip_prefix('/') {
+        return match name {
+            "b" | "i" | "color" | "font" => Some(Tag::Close(name.to_string())),
+            _ => None,
+        };
+    }
+
+    let mut style = style.clone();
+    let (name, value) = match tag.find('=') {
+        None => (tag, ""),
+        Some(index) => (&tag[..index], &tag[index + 1..]),
+    };
+
+    match (name, value) {
+        ("b", "") => {
+            style.bold = true;
+            Some(Tag::Open("b", style))
+        },
+        ("i", "") => {
+            style.italic = true;
+            Some(Tag::Open("i", style))
+        },
+        ("color", value) if value.is_ascii() => {
+            style.color = Some(Color::from_str(value).ok()?);
+            Some(Tag::Open("color", style))
+        },
+        ("font", value) if !value.is_empty() => {
+            style.font = Some(value.to_string());
+            Some(Tag::Open("font", style))
+        },
+        ("icon", value) if !value.is_empty() => Some(Tag::Icon(value.to_string())),
+        _ => None,
+    }
+}
+
+/// Parses the `markup` into a list of styled spans.  Unclosed tags apply to
+/// the end of the text.
+pub fn parse_markup(markup: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut styles: Vec<(&str, SpanStyle)> = vec![("", SpanStyle::default())];
+    let mut text = String::new();
+
+    let mut rest = markup;
+    while let Some(start) = rest.find('[') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(escaped) = rest.strip_prefix("[[") {
+            text.push('[');
+            rest = escaped;
+            continue;
+        }
+
+        let end = match rest.find(']') {
+            None => break,
+            Some(end) => end,
+        };
+
+        let style = &styles.last().unwrap().1;
+        match parse_tag(&rest[1..end], style) {
+            None => text.push_str(&rest[..=end]),
+            Some(tag) => {
+                push_text(&mut spans, &mut text, style);
+                match tag {
+                    Tag::Open(name, style) => styles.push((name, style)),
+                    // closing a tag also closes any tags opened inside it
+                    Tag::Close(name) => {
+                        let open = styles.iter().skip(1).rposition(|(open, _)| *open == name);
+                        if let Some(index) = open {
+                            styles.truncate(index + 1);
+                        }
+                    },
+                    Tag::Icon(id) => spans.push(Span {
+                        content: SpanContent::Icon(id),
+                        style: styles.last().unwrap().1.clone(),
+                    }),
+                }
+            }
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    text.push_str(rest);
+    push_text(&mut spans, &mut text, &styles.last().unwrap().1);
+    spans
+}
+
+fn push_text(spans: &mut Vec<Span>, text: &mut String, style: &SpanStyle) {
+    if text.is_empty() { return; }
+
+    spans.push(Span {
+        content: SpanContent::Text(std::mem::take(text)),
+        style: style.clone(),
+    });
+}
+
+#[derive(Clone)]
+enum Piece {
+    Text { text: String, font: Rc<dyn Font>, color: Color },
+
+    // size is at a scale of 1.0
+    Icon { image: Image, size: Size },
+}
+
+/// Styled spans with their fonts and images looked up in the registries,
+/// ready to be measured and laid out.
+#[derive(Clone)]
+pub struct RichText {
+    pieces: Vec<Piece>,
+    font: Rc<dyn Font>,
+    color: Color,
+}
+
+impl Default for RichText {
+    fn default() -> Self {
+        RichText {
+            pieces: Vec::new(),
+            font: Rc::new(EmptyFont { }),
+            color: Color::default(),
+        }
+    }
+}
+
+impl RichText {
+    pub fn new(spans: &[Span], params: &TextParams, fonts: &FontRegistry,
+               images: &ImageRegistry) -> RichText {
+        let font = fonts.font(&params.font);
+
+        let pieces = spans.iter().map(|span| {
+            match &span.content {
+                SpanContent::Text(text) => Piece::Text {
+                    text: text.to_string(),
+                    font: resolve_font(&span.style, params, fonts),
+                    color: span.style.color.unwrap_or(params.color),
+                },
+                SpanContent::Icon(id) => {
+                    let image = images.image(Some(id));
+                    let size = image.size().unwrap_or_else(|| {
+                        let line_height = font.line_height().ceil() as u32;
+                        Size { width: line_height, height: line_height }
+                    });
+                    Piece::Icon { image, size }
+                }
+            }
+        }).collect();
+
+        RichText {
+            pieces,
+            font,
+            color: params.color,
+        }
+    }
+
+    /// Plain `text`, with no markup, drawn entirely in `font`
+    pub fn plain(text: &str, font: &Rc<dyn Font>, params: &TextParams) -> RichText {
+        RichText {
+            pieces: vec![Piece::Text {
+                text: text.to_string(),
+                font: Rc::clone(font),
+                color: params.color,
+            }],
+            font: Rc::clone(font),
+            color: params.color,
+        }
+    }
+
+    /// Returns the size of the text without any wrapping
+    pub fn measure(&self, params: &TextParams) -> Size {
+        let lines = self.break_lines(params, None);
+        if lines.iter().all(|line| line.is_empty()) { return Size::default(); }
+
+        let mut size = Size::default();
+        for line in lines.iter() {
+            let (ascent, descent) = self.line_metrics(line, params.scale);
+            size.width = size.width.max(line_width(line).ceil() as u32);
+            size.height += (ascent + descent).ceil() as u32;
+        }
+        size
+    }
+
+    /// Breaks the text into lines and positions it within `area`.  Lines are
+    /// broken at each `\n`, and also at spaces if `TextParams::wrap` is set.
+    /// With `TextParams::ellipsis`, lines that don't fit are truncated.
+    pub fn layout(&self, params: &TextParams, area: Rect) -> RichTextLayout {
+        let max_width = area.width as f32;
+        let mut lines = self.break_lines(params, Some(max_width));
+        let mut metrics: Vec<_> = lines.iter().map(|line| self.line_metrics(line, params.scale))
+            .collect();
+
+        if params.ellipsis {
+            let mut height = 0.0;
+            let fit = metrics.iter().take_while(|(ascent, descent)| {
+                height += ascent + descent;
+                height <= area.height as f32
+            }).count().max(1);
+
+            let forced = fit < lines.len();
+            lines.truncate(fit);
+            metrics.truncate(fit);
+
+            let last = lines.len() - 1;
+            for (index, line) in lines.iter_mut().enumerate() {
+                self.truncate(line, params.scale, max_width, forced && index == last);
+            }
+        }
+
+        let total_height: f32 = metrics.iter().map(|(ascent, descent)| ascent + descent).sum();
+        let mut y = match params.vertical_alignment {
+            VerticalAlignment::Top => area.y as f32,
+            VerticalAlignment::Center => area.y as f32 + (area.height as f32 - total_height) / 2.0,
+            VerticalAlignment::Bottom => area.y as f32 + area.height as f32 - total_height,
+        };
+
+        let mut items = Vec::new();
+        let mut size = Size { width: 0, height: total_height.ceil() as u32 };
+        for (line, (ascent, descent)) in lines.into_iter().zip(metrics) {
+            let line = merge(line, params.scale);
+            let width = line_width(&line);
+            size.width = size.width.max(width.ceil() as u32);
+
+            let mut x = match params.horizontal_alignment {
+                HorizontalAlignment::Left => area.x as f32,
+                HorizontalAlignment::Center => area.x as f32 + (max_width - width) / 2.0,
+                HorizontalAlignment::Right => area.x as f32 + max_width - width,
+            };
+
+            for item in line {
+                let (item_ascent, _) = item.metrics(params.scale);
+                let position = Point {
+                    x: x.round() as i32,
+                    y: (y + ascent - item_ascent).round() as i32,
+                };
+                x += item.width;
+
+                items.push(match item.piece {
+                    Piece::Text { text, font, color } => {
+                        let mut params = params.clone();
+                        params.color = color;
+                        PositionedItem::Text { text, position, font, params }
+                    },
+                    Piece::Icon { image, size } => {
+                        PositionedItem::Icon { image, position, size: scale_size(size, params.scale) }
+                    },
+                });
+            }
+
+            y += ascent + descent;
+        }
+
+        RichTextLayout {
+            items,
+            size,
+        }
+    }
+
+    fn line_metrics(&self, line: &[Item], scale: f32) -> (f32, f32) {
+        if line.is_empty() {
+            let ascent = self.font.baseline() * scale;
+            return (ascent, self.font.line_height() * scale - ascent);
+        }
+
+        line.iter().fold((0.0, 0.0), |(ascent, descent): (f32, f32), item| {
+            let (item_ascent, item_height) = item.metrics(scale);
+            (ascent.max(item_ascent), descent.max(item_height - item_ascent))
+        })
+    }
+
+    // breaks lines at each newline, and at spaces if wrapping with a max width
+    fn break_lines(&self, params: &TextParams, max_width: Option<f32>) -> Vec<Vec<Item>> {
+        let max_width = if params.wrap { max_width } else { None };
+        let scale = params.scale;
+
+        let mut lines = vec![Vec::new()];
+        let mut width = 0.0;
+        let mut wrapped = false;
+
+        for token in self.tokens(scale) {
+            let item = match token {
+                Token::Break => {
+                    lines.push(Vec::new());
+                    width = 0.0;
+                    wrapped = false;
+                    continue;
+                },
+                Token::Space(item) => {
+                    // drop spaces at the start of a wrapped line
+                    if wrapped && lines.last().unwrap().is_empty() { continue; }
+
+                    width += item.width;
+                    lines.last_mut().unwrap().push(item);
+                    continue;
+                },
+                Token::Word(item) => item,
+            };
+
+            let max_width = match max_width {
+                None => {
+                    width += item.width;
+                    lines.last_mut().unwrap().push(item);
+                    continue;
+                },
+                Some(max_width) => max_width,
+            };
+
+            let line = lines.last_mut().unwrap();
+            if width + item.width > max_width && line.iter().any(|item| !item.is_space()) {
+                while line.last().is_some_and(|item| item.is_space()) {
+                    line.pop();
+                }
+                lines.push(Vec::new());
+                width = 0.0;
+                wrapped = true;
+            }
+
+            // break words that are too long to fit on a line by themselves
+            let mut item = item;
+            while width == 0.0 && item.width > max_width {
+                let (first, rest) = match item.split(scale, max_width) {
+                    None => break,
+                    Some(split) => split,
+                };
+                lines.last_mut().unwrap().push(first);
+                lines.push(Vec::new());
+                wrapped = true;
+                item = rest;
+            }
+
+            width += item.width;
+            lines.last_mut().unwrap().push(item);
+        }
+
+        lines
+    }
+
+    fn tokens(&self, scale: f32) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        for piece in self.pieces.iter() {
+            let (text, font, color) = match piece {
+                Piece::Icon { .. } => {
+                    tokens.push(Token::Word(Item::new(piece.clone(), scale)));
+                    continue;
+                },
+                Piece::Text { text, font, color } => (text, font, *color),
+            };
+
+            let text_item = |text: &str| {
+                let piece = Piece::Text { text: text.to_string(), font: Rc::clone(font), color };
+                Item::new(piece, scale)
+            };
+
+            let mut start = 0;
+            for (index, c) in text.char_indices() {
+                let is_boundary = c == '\n' || c == ' ';
+                if !is_boundary { continue; }
+
+                if start < index {
+                    tokens.push(Token::Word(text_item(&text[start..index])));
+                }
+                tokens.push(match c {
+                    '\n' => Token::Break,
+                    _ => Token::Space(text_item(" ")),
+                });
+                start = index + c.len_utf8();
+            }
+
+            if start < text.len() {
+                tokens.push(Token::Word(text_item(&text[start..])));
+            }
+        }
+        tokens
+    }
+
+    // shortens the line until it fits with an ellipsis appended.  Lines that
+    // already fit are left as is unless `force` is set
+    fn truncate(&self, line: &mut Vec<Item>, scale: f32, max_width: f32, force: bool) {
+        if !force && line_width(line) <= max_width { return; }
+
+        loop {
+            while line.last().is_some_and(|item| item.is_space()) {
+                line.pop();
+            }
+
+            let (font, color) = match line.iter().rev().find_map(|item| item.font()) {
+                None => (Rc::clone(&self.font), self.color),
+                Some((font, color)) => (Rc::clone(font), color),
+            };
+            let ellipsis = Item::new(Piece::Text { text: ELLIPSIS.to_string(), font, color }, scale);
+
+            if line.is_empty() || line_width(line) + ellipsis.width <= max_width {
+                line.push(ellipsis);
+                return;
+            }
+
+            let last = line.pop().unwrap();
+            if let Piece::Text { mut text, font, color } = last.piece {
+                text.pop();
+                if !text.is_empty() {
+                    line.push(Item::new(Piece::Text { text, font, color }, scale));
+                }
+            }
+        }
+    }
+}
+
+fn resolve_font(style: &SpanStyle, params: &TextParams, fonts: &FontRegistry) -> Rc<dyn Font> {
+    let base = style.font.as_deref().unwrap_or(&params.font);
+    let variant = match (style.bold, style.italic) {
+        (false, false) => return fonts.font(base),
+        (true, false) => format!("{}-bold", base),
+        (false, true) => format!("{}-italic", base),
+        (true, true) => format!("{}-bold-italic", base),
+    };
+
+    match fonts.get(&variant) {
+        None => fonts.font(base),
+        Some(font) => font,
+    }
+}
+
+// returns the byte length of the longest prefix of text that fits, which is
+// always at least one character
+fn fitting_prefix(font: &dyn Font, text: &str, scale: f32, max_width: f32) -> usize {
+    let mut chars = text.char_indices().map(|(index, c)| index + c.len_utf8());
+    let mut end = match chars.next() {
+        None => return 0,
+        Some(end) => end,
+    };
+
+    for next in chars {
+        if font.line_width(&text[..next], scale) > max_width { break; }
+        end = next;
+    }
+    end
+}
+
+fn scale_size(size: Size, scale: f32) -> Size {
+    Size {
+        width: (size.width as f32 * scale).round() as u32,
+        height: (size.height as f32 * scale).round() as u32,
+    }
+}
+
+fn line_width(line: &[Item]) -> f32 {
+    line.iter().map(|item| item.width).sum()
+}
+
+// joins adjacent text with the same font and color into single runs
+fn merge(line: Vec<Item>, scale: f32) -> Vec<Item> {
+    let mut out: Vec<Item> = Vec::new();
+    for item in line {
+        if let (Some(last), Piece::Text { text, font, color }) = (out.last_mut(), &item.piece) {
+            if let Piece::Text { text: last_text, font: last_font, color: last_color } =
+                &mut last.piece {
+                if Rc::ptr_eq(font, last_font) && same_color(*color, *last_color) {
+                    last_text.push_str(text);
+                    last.width = last_font.line_width(last_text, scale);
+                    continue;
+                }
+            }
+        }
+        out.push(item);
+    }
+    out
+}
+
+fn same_color(a: Color, b: Color) -> bool {
+    a.r == b.r && a.g == b.g && a.b == b.b && a.a == b.a
+}
+
+enum Token {
+    Word(Item),
+    Space(Item),
+    Break,
+}
+
+#[derive(Clone)]
+struct Item {
+    piece: Piece,
+    width: f32,
+}
+
+impl Item {
+    fn new(piece: Piece, scale: f32) -> Item {
+        let width = match &piece {
+            Piece::Text { text, font, .. } => font.line_width(text, scale),
+            Piece::Icon { size, .. } => size.width as f32 * scale,
+        };
+        Item { piece, width }
+    }
+
+    fn is_space(&self) -> bool {
+        match &self.piece {
+            Piece::Text { text, .. } => text == " ",
+            Piece::Icon { .. } => false,
+        }
+    }
+
+    fn font(&self) -> Option<(&Rc<dyn Font>, Color)> {
+        match &self.piece {
+            Piece::Text { font, color, .. } => Some((font, *color)),
+            Piece::Icon { .. } => None,
+        }
+    }
+
+    // the distance from the top to the baseline, and the total height
+    fn metrics(&self, scale: f32) -> (f32, f32) {
+        match &self.piece {
+            Piece::Text { font, .. } => (font.baseline() * scale, font.line_height() * scale),
+            Piece::Icon { size, .. } => {
+                let height = size.height as f32 * scale;
+                (height, height)
+            }
+        }
+    }
+
+    // splits text into the longest prefix that fits and the remainder
+    fn split(&self, scale: f32, max_width: f32) -> Option<(Item, Item)> {
+        let (text, font, color) = match &self.piece {
+            Piece::Text { text, font, color } => (text, font, *color),
+            Piece::Icon { .. } => return None,
+        };
+
+        let split = fitting_prefix(font.as_ref(), text, scale, max_width);
+        if split == text.len() { return None; }
+
+        let item = |text: &str| {
+            Item::new(Piece::Text { text: text.to_string(), font: Rc::clone(font), color }, scale)
+        };
+        Some((item(&text[..split]), item(&text[split..])))
+    }
+}
+
+pub enum PositionedItem {
+    Text { text: String, position: Point, font: Rc<dyn Font>, params: TextParams },
+    Icon { image: Image, position: Point, size: Size },
+}
+
+/// Rich text broken into lines and positioned within an area
+#[derive(Default)]
+pub struct RichTextLayout {
+    items: Vec<PositionedItem>,
+    size: Size,
+}
+
+impl RichTextLayout {
+    pub fn items(&self) -> &[PositionedItem] { &self.items }
+
+    /// The size of the area covered by all lines
+    pub fn size(&self) -> Size { self.size }
+
+    pub fn draw(&self, renderer: &mut dyn Renderer) {
+        for item in self.items.iter() {
+            match item {
+                PositionedItem::Text { text, position, font, params } => {
+                    renderer.draw_text(&TextRun {
+                        text,
+                        position: *position,
+                        params,
+                        font: font.as_ref(),
+                    });
+                },
+                PositionedItem::Icon { image, position, size } => {
+                    image.draw(renderer, *position, *size);
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // describes each span as its content followed by its style, e.g. "text b i f00 sans"
+    fn describe(markup: &str) -> Vec<String> {
+        parse_markup(markup).iter().map(|span| {
+            let mut out = match &span.content {
+                SpanContent::Text(text) => text.to_string(),
+                SpanContent::Icon(id) => format!("<{}>", id),
+            };
+            let style = &span.style;
+            if style.bold { out.push_str(" b"); }
+            if style.italic { out.push_str(" i"); }
+            if let Some(color) = style.color {
+                out.push_str(&format!(" {}{}{}", channel(color.r), channel(color.g),
+                                      channel(color.b)));
+            }
+            if let Some(font) = &style.font {
+                out.push_str(&format!(" {}", font));
+            }
+            out
+        }).collect()
+    }
+
+    fn channel(value: f32) -> String {
+        format!("{:x}", (value * 15.0).round() as u32)
+    }
+
+    #[test]
+    fn plain_text_is_one_span() {
+        assert_eq!(describe("plain text"), vec!["plain text"]);
+        assert!(describe("").is_empty());
+    }
+
+    #[test]
+    fn nested_tags() {
+        assert_eq!(describe("a [b]bold [i]both[/i] bold[/b] a"),
+                   vec!["a ", "bold  b", "both b i", " bold b", " a"]);
+        assert_eq!(describe("[color=f00]red [font=mono]mono[/font][/color]"),
+                   vec!["red  f00", "mono f00 mono"]);
+    }
+
+    #[test]
+    fn closing_tag_closes_tags_opened_inside_it() {
+        assert_eq!(describe("[b][i]both[/b]none"), vec!["both b i", "none"]);
+    }
+
+    #[test]
+    fn unclosed_tags_apply_to_the_end() {
+        assert_eq!(describe("a [i]rest"), vec!["a ", "rest i"]);
+    }
+
+    #[test]
+    fn escaped_brackets() {
+        assert_eq!(describe("[[b]not bold"), vec!["[b]not bold"]);
+        assert_eq!(describe("a [[[b]b[/b]"), vec!["a [", "b b"]);
+    }
+
+    #[test]
+    fn unmatched_closing_tags_are_ignored() {
+        assert_eq!(describe("a[/b] b[/color]"), vec!["a", " b"]);
+        assert_eq!(describe("[i]a[/b]b"), vec!["a i", "b i"]);
+    }
+
+    #[test]
+    fn invalid_tags_are_text() {
+        assert_eq!(describe("[unknown]a [color=xyz]b [/unknown]"),
+                   vec!["[unknown]a [color=xyz]b [/unknown]"]);
+        assert_eq!(describe("unterminated [b"), vec!["unterminated [b"]);
+    }
+
+    #[test]
+    fn icons() {
+        assert_eq!(describe("[icon=heart] 3 [b][icon=star][/b]"),
+                   vec!["<heart>", " 3 ", "<star> b"]);
+        assert_eq!(describe("[icon=]"), vec!["[icon=]"]);
+    }
+}
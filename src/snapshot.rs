@@ -0,0 +1,217 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+//! Golden image testing for themes.  A `SnapshotTest` loads a theme file,
+//! builds and lays out a `WidgetTree`, renders it with the `SoftwareRenderer`
+//! and compares the result against a stored PNG.
+//!
+//! When a golden image does not match, the rendered image and a diff image,
+//! with mismatched pixels in red, are written next to it.  Set the
+//! `THRUST_UI_UPDATE_SNAPSHOTS` environment variable to write the rendered
+//! image as the new golden image instead of comparing.
+
+use std::env;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::color::Color;
+use crate::png_image::{load_texture, write_png};
+use crate::software_renderer::SoftwareRenderer;
+use crate::theme_builder::ThemeBuilderSet;
+use crate::widget::{EmptyWidget, Size};
+use crate::widget_tree::WidgetTree;
+
+pub const UPDATE_SNAPSHOTS_VAR: &str = "THRUST_UI_UPDATE_SNAPSHOTS";
+
+pub struct SnapshotTest {
+    pub theme_file: PathBuf,
+    pub size: Size,
+
+    /// The theme ID of the root widget of the tree
+    pub root_theme: String,
+
+    /// The color the framebuffer is cleared to before drawing
+    pub clear_color: Color,
+
+    /// The maximum difference allowed in any channel of a pixel before it
+    /// is considered to not match
+    pub tolerance: u8,
+
+    /// The number of pixels that may fail to match without failing the test
+    pub max_mismatched: usize,
+}
+
+impl SnapshotTest {
+    pub fn new<P: AsRef<Path>>(theme_file: P, size: Size) -> SnapshotTest {
+        SnapshotTest {
+            theme_file: theme_file.as_ref().to_path_buf(),
+            size,
+            root_theme: "root".to_string(),
+            clear_color: Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 },
+            tolerance: 2,
+            max_mismatched: 0,
+        }
+    }
+
+    /// Renders the theme, calling `setup` to add any widgets defined in code
+    /// before the tree is laid out and drawn.  The images and fonts defined in
+    /// the theme file are available to the tree.
+    pub fn render<F: FnOnce(&mut WidgetTree)>(&self, setup: F) -> Result<SoftwareRenderer, Error> {
+        let themes = ThemeBuilderSet::load(&self.theme_file)?.create_theme_set()?;
+
+        let mut root = EmptyWidget::new();
+        root.set_theme(self.root_theme.clone());
+        let mut tree = WidgetTree::new(root, themes);
+
+        setup(&mut tree);
+
+        tree.set_size(self.size);
+        tree.layout();
+
+        let mut renderer = SoftwareRenderer::new(self.size.width, self.size.height);
+        renderer.clear(self.clear_color);
+        tree.draw(&mut renderer);
+        Ok(renderer)
+    }
+
+    /// Renders the theme as in `render` and compares the result against the
+    /// PNG at `golden`, returning an error describing the mismatch if they differ.
+    pub fn check<P, F>(&self, golden: P, setup: F) -> Result<(), Error>
+        where P: AsRef<Path>, F: FnOnce(&mut WidgetTree) {
+        let renderer = self.render(setup)?;
+        self.compare(&renderer, golden)
+    }
+
+    /// Compares the contents of `renderer` against the PNG at `golden`.
+    pub fn compare<P: AsRef<Path>>(&self, renderer: &SoftwareRenderer,
+                                   golden: P) -> Result<(), Error> {
+        let golden = golden.as_ref();
+        let (width, height) = (renderer.width(), renderer.height());
+
+        if env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+            return write_png(golden, width, height, renderer.pixels());
+        }
+
+        if !golden.exists() {
+            let actual = sibling(golden, "actual");
+            write_png(&actual, width, height, renderer.pixels())?;
+            return Err(Error::new(ErrorKind::NotFound,
+                                  format!("Golden image {} does not exist.  Rendered output \
+                                          written to {}", golden.display(), actual.display())));
+        }
+
+        let golden_texture = load_texture(golden)?;
+        let (golden_width, golden_height) = (golden_texture.width(), golden_texture.height());
+        let expected = golden_texture.pixels();
+        if golden_width != width || golden_height != height {
+            return Err(Error::new(ErrorKind::InvalidData,
+                                  format!("Golden image {} is {}x{} but rendered output is {}x{}",
+                                          golden.display(), golden_width, golden_height,
+                                          width, height)));
+        }
+
+        let mut diff = Vec::with_capacity(expected.len());
+        let mut mismatched = 0;
+        for (actual, expected) in renderer.pixels().chunks(4).zip(expected.chunks(4)) {
+            let matches = actual.iter().zip(expected.iter())
+                .all(|(a, e)| (*a as i32 - *e as i32).abs() <= self.tolerance as i32);
+
+            if matches {
+                // dimmed grayscale of the expected image for context
+                let gray = ((expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 6) as u8;
+                diff.extend_from_slice(&[gray, gray, gray, 255]);
+            } else {
+                mismatched += 1;
+                diff.extend_from_slice(&[255, 0, 0, 255]);
+            }
+        }
+
+        if mismatched <= self.max_mismatched { return Ok(()); }
+
+        let actual = sibling(golden, "actual");
+        let diff_file = sibling(golden, "diff");
+        write_png(&actual, width, height, renderer.pixels())?;
+        write_png(&diff_file, width, height, &diff)?;
+
+        Err(Error::new(ErrorKind::InvalidData,
+                       format!("{} pixels differ from golden image {}.  Rendered output \
+                               written to {} and diff to {}", mismatched, golden.display(),
+                               actual.display(), diff_file.display())))
+    }
+}
+
+// "dir/name.png" -> "dir/name.suffix.png"
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::render::Renderer;
+    use crate::widget::Rect;
+
+    fn filled(color: Color) -> SoftwareRenderer {
+        let mut renderer = SoftwareRenderer::new(2, 2);
+        renderer.fill_rect(Rect { x: 0, y: 0, width: 1, height: 2 }, color);
+        renderer
+    }
+
+    fn pixels<P: AsRef<Path>>(path: P) -> Vec<u8> {
+        load_texture(path).unwrap().pixels().to_vec()
+    }
+
+    // all cases share one test as they depend on the process wide environment
+    #[test]
+    fn compare_against_golden() {
+        let dir = env::temp_dir().join(format!("thrust-ui-snapshot-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let golden = dir.join("golden.png");
+        let test = SnapshotTest::new("unused.yml", Size { width: 2, height: 2 });
+        env::remove_var(UPDATE_SNAPSHOTS_VAR);
+
+        let red = filled(Color { r: 1.0, g: 0.0, b: 0.0, a: 1.0 });
+        let blue = filled(Color { r: 0.0, g: 0.0, b: 1.0, a: 1.0 });
+
+        let err = test.compare(&red, &golden).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(pixels(dir.join("golden.actual.png")), red.pixels());
+
+        write_png(&golden, 2, 2, blue.pixels()).unwrap();
+        test.compare(&blue, &golden).unwrap();
+
+        let err = test.compare(&red, &golden).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("2 pixels differ"));
+        assert_eq!(pixels(dir.join("golden.actual.png")), red.pixels());
+        assert_eq!(pixels(dir.join("golden.diff.png")), vec![
+            255, 0, 0, 255, 0, 0, 0, 255,
+            255, 0, 0, 255, 0, 0, 0, 255,
+        ]);
+
+        env::set_var(UPDATE_SNAPSHOTS_VAR, "1");
+        let result = test.compare(&red, &golden);
+        env::remove_var(UPDATE_SNAPSHOTS_VAR);
+        result.unwrap();
+        assert_eq!(pixels(&golden), red.pixels());
+        test.compare(&red, &golden).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+use crate::color::Color;
+use crate::render::{draw_glyphs, Renderer, TextRun, Texture, UvRect};
+use crate::widget::Rect;
+
+/// A `Renderer` that rasterizes on the CPU into an RGBA buffer, with 8 bits
+/// per channel in row major order.  Requires no GPU or windowing system, so is
+/// suitable for headless environments and for capturing screenshots.
+pub struct SoftwareRenderer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    clip_stack: Vec<Rect>,
+}
+
+impl SoftwareRenderer {
+    /// Creates a new renderer with a fully transparent framebuffer of the
+    /// specified size.
+    pub fn new(width: u32, height: u32) -> SoftwareRenderer {
+        SoftwareRenderer {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+            clip_stack: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+
+    pub fn height(&self) -> u32 { self.height }
+
+    pub fn pixels(&self) -> &[u8] { &self.pixels }
+
+    pub fn into_pixels(self) -> Vec<u8> { self.pixels }
+
+    /// Returns the RGBA value of the pixel at the specified coordinates.
+    /// Panics if the coordinates are outside the framebuffer.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        assert!(x < self.width && y < self.height);
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    /// Sets every pixel to `color`, ignoring the current clip area.
+    pub fn clear(&mut self, color: Color) {
+        let value = to_bytes(color);
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&value);
+        }
+    }
+
+    fn clip(&self) -> Rect {
+        let screen = Rect { x: 0, y: 0, width: self.width, height: self.height };
+        match self.clip_stack.last() {
+            None => screen,
+            Some(clip) => clip.intersect(&screen),
+        }
+    }
+
+    // source over blending of a straight alpha color onto the framebuffer
+    fn blend(&mut self, x: u32, y: u32, src: [f32; 4]) {
+        let alpha = src[3];
+        if alpha <= 0.0 { return; }
+
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let dst = &mut self.pixels[i..i + 4];
+        let dst_alpha = dst[3] as f32 / 255.0;
+        let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+
+        for c in 0..3 {
+            let d = dst[c] as f32 / 255.0;
+            let value = (src[c] * alpha + d * dst_alpha * (1.0 - alpha)) / out_alpha;
+            dst[c] = to_byte(value);
+        }
+        dst[3] = to_byte(out_alpha);
+    }
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn to_bytes(color: Color) -> [u8; 4] {
+    [to_byte(color.r), to_byte(color.g), to_byte(color.b), to_byte(color.a)]
+}
+
+impl Renderer for SoftwareRenderer {
+    fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let area = rect.intersect(&self.clip());
+        if area.is_empty() { return; }
+
+        let src = [color.r, color.g, color.b, color.a];
+        for y in area.y..(area.y + area.height as i32) {
+            for x in area.x..(area.x + area.width as i32) {
+                self.blend(x as u32, y as u32, src);
+            }
+        }
+    }
+
+    fn draw_quad(&mut self, texture: &Texture, rect: Rect, uv: UvRect, color: Color) {
+        let area = rect.intersect(&self.clip());
+        if area.is_empty() { return; }
+
+        let tex_width = texture.width() as f32;
+        let tex_height = texture.height() as f32;
+
+        for y in area.y..(area.y + area.height as i32) {
+            // sample at the pixel center, nearest neighbor
+            let v = uv.y + uv.height * ((y - rect.y) as f32 + 0.5) / rect.height as f32;
+            let ty = (v * tex_height).floor().max(0.0) as u32;
+
+            for x in area.x..(area.x + area.width as i32) {
+                let u = uv.x + uv.width * ((x - rect.x) as f32 + 0.5) / rect.width as f32;
+                let tx = (u * tex_width).floor().max(0.0) as u32;
+
+                let texel = texture.pixel(tx, ty);
+                let src = [
+                    texel[0] as f32 / 255.0 * color.r,
+                    texel[1] as f32 / 255.0 * color.g,
+                    texel[2] as f32 / 255.0 * color.b,
+                    texel[3] as f32 / 255.0 * color.a,
+                ];
+                self.blend(x as u32, y as u32, src);
+            }
+        }
+    }
+
+    fn draw_text(&mut self, run: &TextRun) {
+        draw_glyphs(self, run);
+    }
+
+    fn push_clip(&mut self, rect: Rect) {
+        let clip = match self.clip_stack.last() {
+            None => rect,
+            Some(current) => current.intersect(&rect),
+        };
+        self.clip_stack.push(clip);
+    }
+
+    fn pop_clip(&mut self) {
+        self.clip_stack.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Color = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect { Rect { x, y, width, height } }
+
+    #[test]
+    fn fill_rect_blends_partly_transparent_colors() {
+        let mut renderer = SoftwareRenderer::new(2, 1);
+        renderer.fill_rect(rect(0, 0, 1, 1), Color { r: 0.0, g: 0.0, b: 1.0, a: 1.0 });
+        renderer.fill_rect(rect(0, 0, 2, 1), Color { r: 1.0, g: 0.0, b: 0.0, a: 0.5 });
+
+        assert_eq!(renderer.pixel(0, 0), [128, 0, 128, 255]);
+        assert_eq!(renderer.pixel(1, 0), [255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn fill_rect_stays_within_the_framebuffer() {
+        let mut renderer = SoftwareRenderer::new(3, 3);
+        renderer.fill_rect(rect(-2, 1, 4, 10), WHITE);
+
+        assert_eq!(renderer.pixel(0, 0), [0, 0, 0, 0]);
+        assert_eq!(renderer.pixel(0, 1), [255, 255, 255, 255]);
+        assert_eq!(renderer.pixel(1, 2), [255, 255, 255, 255]);
+        assert_eq!(renderer.pixel(2, 2), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn nested_clips_intersect_until_popped() {
+        let mut renderer = SoftwareRenderer::new(4, 4);
+        let filled = |renderer: &SoftwareRenderer| -> Vec<(u32, u32)> {
+            (0..4).flat_map(|y| (0..4).map(move |x| (x, y)))
+                .filter(|&(x, y)| renderer.pixel(x, y)[3] != 0).collect()
+        };
+
+        renderer.push_clip(rect(0, 0, 3, 3));
+        renderer.push_clip(rect(1, 1, 3, 3));
+        renderer.fill_rect(rect(0, 0, 4, 4), WHITE);
+        assert_eq!(filled(&renderer), vec![(1, 1), (2, 1), (1, 2), (2, 2)]);
+
+        renderer.pop_clip();
+        renderer.fill_rect(rect(0, 0, 4, 4), WHITE);
+        assert_eq!(filled(&renderer).len(), 9);
+        assert_eq!(renderer.pixel(3, 3), [0, 0, 0, 0]);
+
+        renderer.pop_clip();
+        renderer.fill_rect(rect(0, 0, 4, 4), WHITE);
+        assert_eq!(filled(&renderer).len(), 16);
+    }
+}
@@ -0,0 +1,146 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+//! Breaking text into lines within an area and aligning it according to
+//! `TextParams`.  Plain text is laid out in the same way as rich text with a
+//! single span, see `RichText`.
+
+use std::rc::Rc;
+
+use crate::font::Font;
+use crate::render::Renderer;
+use crate::rich_text::{PositionedItem, RichText, RichTextLayout};
+use crate::theme::TextParams;
+use crate::widget::{Point, Rect, Size};
+
+/// Appended to text that is truncated when `TextParams::ellipsis` is set
+pub const ELLIPSIS: &str = "...";
+
+/// A single line of laid out text, with its top left corner at `position`
+#[derive(Debug, Clone, Default)]
+pub struct TextLine {
+    pub text: String,
+    pub position: Point,
+    pub width: u32,
+}
+
+/// Text broken into lines and positioned within an area.  Lines are broken at
+/// each `\n`, and also at word boundaries if `TextParams::wrap` is set.  With
+/// `TextParams::ellipsis`, lines that don't fit are truncated instead of
+/// overflowing the area.
+#[derive(Default)]
+pub struct TextLayout {
+    lines: Vec<TextLine>,
+    layout: RichTextLayout,
+}
+
+impl TextLayout {
+    pub fn new(font: &Rc<dyn Font>, text: &str, params: &TextParams, area: Rect) -> TextLayout {
+        let layout = RichText::plain(text, font, params).layout(params, area);
+
+        // with a single font and color, each line is one text item
+        let lines = layout.items().iter().filter_map(|item| match item {
+            PositionedItem::Text { text, position, font, params } => Some(TextLine {
+                text: text.to_string(),
+                position: *position,
+                width: font.line_width(text, params.scale).ceil() as u32,
+            }),
+            PositionedItem::Icon { .. } => None,
+        }).collect();
+
+        TextLayout {
+            lines,
+            layout,
+        }
+    }
+
+    pub fn lines(&self) -> &[TextLine] { &self.lines }
+
+    /// The size of the area covered by all lines
+    pub fn size(&self) -> Size { self.layout.size() }
+
+    /// Draws each line as a `TextRun`
+    pub fn draw(&self, renderer: &mut dyn Renderer) {
+        self.layout.draw(renderer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::{HorizontalAlignment, VerticalAlignment};
+
+    // every character is 6 pixels wide
+    struct FixedFont;
+
+    impl Font for FixedFont {
+        fn line_height(&self) -> f32 { 10.0 }
+
+        fn baseline(&self) -> f32 { 8.0 }
+
+        fn advance(&self, _c: char) -> f32 { 6.0 }
+    }
+
+    fn layout(text: &str, params: &TextParams, width: u32, height: u32) -> Vec<(String, i32, i32)> {
+        let font: Rc<dyn Font> = Rc::new(FixedFont);
+        let area = Rect { x: 0, y: 0, width, height };
+        TextLayout::new(&font, text, params, area).lines().iter()
+            .map(|line| (line.text.clone(), line.position.x, line.position.y))
+            .collect()
+    }
+
+    fn params(wrap: bool, ellipsis: bool) -> TextParams {
+        TextParams {
+            horizontal_alignment: HorizontalAlignment::Left,
+            vertical_alignment: VerticalAlignment::Top,
+            scale: 1.0,
+            wrap,
+            ellipsis,
+            ..Default::default()
+        }
+    }
+
+    fn line(text: &str, x: i32, y: i32) -> (String, i32, i32) { (text.to_string(), x, y) }
+
+    #[test]
+    fn wraps_at_spaces_and_newlines() {
+        assert_eq!(layout("one two three\nfour", &params(true, false), 50, 100), vec![
+            line("one two", 0, 0), line("three", 0, 10), line("four", 0, 20),
+        ]);
+        assert_eq!(layout("abcdefghij", &params(true, false), 24, 100), vec![
+            line("abcd", 0, 0), line("efgh", 0, 10), line("ij", 0, 20),
+        ]);
+    }
+
+    #[test]
+    fn ellipsis_truncates_lines_that_do_not_fit() {
+        assert_eq!(layout("truncated text", &params(false, true), 50, 10),
+                   vec![line("trunc...", 0, 0)]);
+        assert_eq!(layout("one two three", &params(true, true), 50, 15),
+                   vec![line("one t...", 0, 0)]);
+    }
+
+    #[test]
+    fn aligns_lines_within_the_area() {
+        let params = TextParams {
+            horizontal_alignment: HorizontalAlignment::Right,
+            vertical_alignment: VerticalAlignment::Center,
+            ..params(false, false)
+        };
+        assert_eq!(layout("ab\nabcd", &params, 30, 40),
+                   vec![line("ab", 18, 10), line("abcd", 6, 20)]);
+    }
+}
@@ -15,79 +15,100 @@
 //  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
 
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::str::FromStr;
 
+use serde::{Deserialize, Deserializer};
 use serde_derive::{Deserialize};
 
 use crate::color::Color;
-use crate::widget::{Size, Point};
+use crate::expression::Expression;
+use crate::font::Font;
+use crate::render::Texture;
+use crate::widget::{Size, Point, Rect};
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Default, Debug, Clone, Copy)]
 #[serde(deny_unknown_fields)]
 pub enum HorizontalAlignment {
+    #[default]
     Left,
     Center,
     Right,
 }
 
-impl Default for HorizontalAlignment {
-    fn default() -> Self { HorizontalAlignment::Left }
-}
-
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Default, Debug, Clone, Copy)]
 #[serde(deny_unknown_fields)]
 pub enum VerticalAlignment {
     Top,
+    #[default]
     Center,
     Bottom,
 }
 
-impl Default for VerticalAlignment {
-    fn default() -> Self { VerticalAlignment::Center }
-}
-
-#[derive(Deserialize, Debug, Clone, Copy)]
-#[serde(deny_unknown_fields)]
+/// How a widget's width or height is computed.  In theme files, any value other
+/// than one of the variant names is parsed as a `Custom` expression.
+#[derive(Default, Debug, Clone)]
 pub enum SizeRelative {
+    #[default]
     Zero,
     Parent,
     ChildMax,
     ChildSum,
-    Custom,
+    Custom(Expression),
 }
 
-impl Default for SizeRelative {
-    fn default() -> Self {
-        SizeRelative::Zero
+impl<'de> Deserialize<'de> for SizeRelative {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Ok(match text.as_str() {
+            "Zero" => SizeRelative::Zero,
+            "Parent" => SizeRelative::Parent,
+            "ChildMax" => SizeRelative::ChildMax,
+            "ChildSum" => SizeRelative::ChildSum,
+            _ => SizeRelative::Custom(de_expression::<D>(&text)?),
+        })
     }
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
-#[serde(deny_unknown_fields)]
+/// How a widget's x or y position is computed.  In theme files, any value other
+/// than one of the variant names is parsed as a `Custom` expression.
+#[derive(Default, Debug, Clone)]
 pub enum PositionRelative {
+    #[default]
     Zero,
     Center,
     Max,
-    Custom,
+    Custom(Expression),
 }
 
-impl Default for PositionRelative {
-    fn default() -> Self { PositionRelative::Zero }
+impl<'de> Deserialize<'de> for PositionRelative {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Ok(match text.as_str() {
+            "Zero" => PositionRelative::Zero,
+            "Center" => PositionRelative::Center,
+            "Max" => PositionRelative::Max,
+            _ => PositionRelative::Custom(de_expression::<D>(&text)?),
+        })
+    }
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+fn de_expression<'de, D: Deserializer<'de>>(text: &str) -> Result<Expression, D::Error> {
+    use serde::de::Error;
+    Expression::from_str(text).map_err(|err| D::Error::custom(err.to_string()))
+}
+
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
 pub enum LayoutKind {
+    #[default]
     Normal,
     BoxVertical,
     BoxHorizontal,
     Grid,
 }
 
-impl Default for LayoutKind {
-    fn default() -> Self { LayoutKind::Normal }
-}
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Relative {
     pub x: PositionRelative,
     pub y: PositionRelative,
@@ -103,6 +124,13 @@ pub struct TextParams {
     pub color: Color,
     pub scale: f32,
     pub font: String,
+
+    /// Whether text is broken into multiple lines at word boundaries to fit
+    /// within the widget
+    pub wrap: bool,
+
+    /// Whether text that does not fit within the widget is truncated with an ellipsis
+    pub ellipsis: bool,
 }
 
 impl Default for TextParams {
@@ -113,6 +141,8 @@ impl Default for TextParams {
             color: Color::default(),
             scale: 1.0,
             font: "Default".to_string(),
+            wrap: false,
+            ellipsis: false,
         }
     }
 }
@@ -137,28 +167,118 @@ pub struct Border {
     pub right: u32,
 }
 
-pub const DEFAULT_THEME_ID: &'static str = "default";
+/// Limits on the size of a widget, applied after its size has been computed
+/// from the `relative` and `size` fields.  A max of `None` is unbounded.
+#[derive(Deserialize, Default, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct SizeLimits {
+    pub min_width: u32,
+    pub min_height: u32,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+}
+
+impl SizeLimits {
+    pub fn clamp_width(&self, width: u32) -> u32 {
+        clamp(width, self.min_width, self.max_width)
+    }
 
-#[derive(Deserialize, Debug, Copy, Clone)]
+    pub fn clamp_height(&self, height: u32) -> u32 {
+        clamp(height, self.min_height, self.max_height)
+    }
+}
+
+fn clamp(value: u32, min: u32, max: Option<u32>) -> u32 {
+    let value = match max {
+        None => value,
+        Some(max) => value.min(max),
+    };
+    value.max(min)
+}
+
+/// Weights used by box layouts to share out the space along the box axis.
+/// Any space left over after the children have been sized is divided between
+/// them in proportion to `grow`, while any overflow is taken back in proportion
+/// to `shrink` multiplied by each child's size.
+#[derive(Deserialize, Default, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct Flex {
+    pub grow: f32,
+    pub shrink: f32,
+}
+
+/// The tracks (columns or rows) of a `Grid` layout.  Either a count of
+/// equally sized tracks sharing the available space, or a list of fixed sizes.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum GridTracks {
+    Count(u32),
+    Sizes(Vec<u32>),
+}
+
+impl Default for GridTracks {
+    fn default() -> Self { GridTracks::Count(1) }
+}
+
+impl GridTracks {
+    pub fn len(&self) -> usize {
+        match self {
+            GridTracks::Count(count) => *count as usize,
+            GridTracks::Sizes(sizes) => sizes.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Grid {
+    pub columns: GridTracks,
+    pub rows: GridTracks,
+}
+
+/// The cell that a child of a `Grid` layout occupies, with spans in number of tracks.
+/// If the `column` or `row` is not specified, the child is placed in the first
+/// free cells that fit its spans.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct GridCell {
+    pub column: Option<u32>,
+    pub row: Option<u32>,
+    pub column_span: u32,
+    pub row_span: u32,
+}
+
+impl Default for GridCell {
+    fn default() -> Self {
+        GridCell { column: None, row: None, column_span: 1, row_span: 1 }
+    }
+}
+
+pub const DEFAULT_THEME_ID: &str = "default";
+
+#[derive(Deserialize, Default, Debug, Copy, Clone)]
 pub enum Kind {
+    #[default]
     Ref, // a reference to a widget that will be added in rust code, or
          // simply used as a building block for another theme item
     Label, // a widget showing static text - defined purely in the theme
     Container, // a widget holding other widgets - defined purely in the theme
 }
 
-impl Default for Kind {
-    fn default() -> Self { Kind::Ref }
-}
-
 #[derive(Debug)]
 pub struct Theme {
     pub id: String,
     pub kind: Kind,
     pub layout: LayoutKind,
     pub layout_spacing: Border,
+    pub grid: Grid,
+    pub grid_cell: Option<GridCell>,
     pub border: Border,
     pub size: Size,
+    pub size_limits: SizeLimits,
+    pub flex: Flex,
     pub position: Point,
     pub relative: Relative,
 
@@ -179,8 +299,12 @@ impl Default for Theme {
             id: DEFAULT_THEME_ID.to_string(),
             layout: LayoutKind::Normal,
             layout_spacing: Border::default(),
+            grid: Grid::default(),
+            grid_cell: None,
             border: Border::default(),
             size: Size::default(),
+            size_limits: SizeLimits::default(),
+            flex: Flex::default(),
             position: Point::default(),
             relative: Relative::default(),
             text: None,
@@ -195,18 +319,54 @@ impl Default for Theme {
     }
 }
 
+/// A named image defined in the theme file, as a region of a texture.  If
+/// `insets` are specified, the image is drawn as a nine slice.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ImageDefinition {
+    pub texture: String,
+
+    /// The region of the texture in pixels, or the whole texture if not specified
+    pub region: Option<Rect>,
+    pub insets: Option<Border>,
+}
+
 #[derive(Debug)]
 pub struct ThemeSet {
     themes: HashMap<String, Theme>,
+    images: HashMap<String, ImageDefinition>,
+    textures: HashMap<String, Rc<Texture>>,
+    fonts: HashMap<String, Rc<dyn Font>>,
 }
 
 impl ThemeSet {
-    pub(crate) fn new(themes: HashMap<String, Theme>) -> ThemeSet {
+    pub(crate) fn new(themes: HashMap<String, Theme>,
+                      images: HashMap<String, ImageDefinition>,
+                      textures: HashMap<String, Rc<Texture>>,
+                      fonts: HashMap<String, Rc<dyn Font>>) -> ThemeSet {
         ThemeSet {
             themes,
+            images,
+            textures,
+            fonts,
         }
     }
 
+    /// Returns the fonts loaded from the `fonts` section of the theme file
+    pub fn fonts(&self) -> &HashMap<String, Rc<dyn Font>> {
+        &self.fonts
+    }
+
+    /// Returns the textures loaded from the `textures` section of the theme file
+    pub fn textures(&self) -> &HashMap<String, Rc<Texture>> {
+        &self.textures
+    }
+
+    /// Returns the images defined in the `images` section of the theme file
+    pub fn image_definitions(&self) -> &HashMap<String, ImageDefinition> {
+        &self.images
+    }
+
     pub fn contains(&self, id: &str) -> bool {
         self.themes.contains_key(id)
     }
@@ -15,17 +15,26 @@
 //  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
 
 use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
 use std::str::FromStr;
 use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 use serde::{Deserialize, Deserializer};
+use serde::de::{MapAccess, Visitor};
 use serde_derive::Deserialize;
 
+use crate::bitmap_font::load_bitmap_font;
 use crate::color::Color;
+use crate::font::Font;
+use crate::png_image::load_texture;
 use crate::theme::*;
+use crate::truetype_font::{load_truetype_font, DEFAULT_FONT_SIZE};
 use crate::widget::{Size, Point};
 
-#[derive(Deserialize, Default, Debug, Clone, Copy)]
+#[derive(Deserialize, Default, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct RelativeBuilder {
     x: Option<PositionRelative>,
@@ -55,10 +64,10 @@ impl RelativeBuilder {
                 to.replace(from.clone());
             },
             Some(to) => {
-                to.x = to.x.or(from.x);
-                to.y = to.y.or(from.y);
-                to.width = to.width.or(from.width);
-                to.height = to.height.or(from.height);
+                if to.x.is_none() { to.x = from.x.clone(); }
+                if to.y.is_none() { to.y = from.y.clone(); }
+                if to.width.is_none() { to.width = from.width.clone(); }
+                if to.height.is_none() { to.height = from.height.clone(); }
             }
         }
     }
@@ -75,6 +84,8 @@ pub struct TextParamsBuilder {
 
     scale: Option<f32>,
     font: Option<String>,
+    wrap: Option<bool>,
+    ellipsis: Option<bool>,
 }
 
 impl TextParamsBuilder {
@@ -85,6 +96,8 @@ impl TextParamsBuilder {
             color: self.color.unwrap_or_default(),
             scale: self.scale.unwrap_or(1.0),
             font: self.font.unwrap_or("default".to_string()),
+            wrap: self.wrap.unwrap_or_default(),
+            ellipsis: self.ellipsis.unwrap_or_default(),
         }
     }
 
@@ -100,9 +113,11 @@ impl TextParamsBuilder {
             }, Some(to) => {
                 to.horizontal_alignment = to.horizontal_alignment.or(from.horizontal_alignment);
                 to.vertical_alignment = to.vertical_alignment.or(from.vertical_alignment);
-                if to.color.is_none() { to.color = from.color.clone(); }
+                if to.color.is_none() { to.color = from.color; }
                 to.scale = to.scale.or(from.scale);
                 if to.font.is_none() { to.font = from.font.clone(); }
+                to.wrap = to.wrap.or(from.wrap);
+                to.ellipsis = to.ellipsis.or(from.ellipsis);
             }
         }
     }
@@ -122,6 +137,31 @@ where D:Deserializer<'de> {
     })
 }
 
+// Children are kept in the order they are declared, as this is the order they
+// are added to the tree and laid out in
+fn de_children<'de, D>(deserializer: D) -> Result<Vec<(String, ThemeBuilder)>, D::Error>
+where D: Deserializer<'de> {
+    struct ChildrenVisitor;
+
+    impl<'de> Visitor<'de> for ChildrenVisitor {
+        type Value = Vec<(String, ThemeBuilder)>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a map of child themes")
+        }
+
+        fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+            let mut children = Vec::new();
+            while let Some(entry) = map.next_entry()? {
+                children.push(entry);
+            }
+            Ok(children)
+        }
+    }
+
+    deserializer.deserialize_map(ChildrenVisitor)
+}
+
 #[derive(Deserialize, Default, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct ThemeBuilder {
@@ -129,8 +169,12 @@ pub struct ThemeBuilder {
     kind: Option<Kind>,
     layout: Option<LayoutKind>,
     layout_spacing: Option<Border>,
+    grid: Option<Grid>,
+    grid_cell: Option<GridCell>,
     border: Option<Border>,
     size: Option<Size>,
+    size_limits: Option<SizeLimits>,
+    flex: Option<Flex>,
     position: Option<Point>,
     relative: Option<RelativeBuilder>,
 
@@ -142,8 +186,8 @@ pub struct ThemeBuilder {
     #[serde(default)]
     custom: HashMap<String, String>,
 
-    #[serde(default)]
-    children: HashMap<String, ThemeBuilder>,
+    #[serde(default, deserialize_with="de_children")]
+    children: Vec<(String, ThemeBuilder)>,
 
     #[serde(skip)]
     children_ids: Vec<String>,
@@ -157,8 +201,12 @@ impl ThemeBuilder {
             id,
             layout: self.layout.unwrap_or_default(),
             layout_spacing: self.layout_spacing.unwrap_or_default(),
+            grid: self.grid.unwrap_or_default(),
+            grid_cell: self.grid_cell,
             border: self.border.unwrap_or_default(),
             size: self.size.unwrap_or_default(),
+            size_limits: self.size_limits.unwrap_or_default(),
+            flex: self.flex.unwrap_or_default(),
             position: self.position.unwrap_or_default(),
             relative: match self.relative {
                 None => Relative::default(),
@@ -183,11 +231,61 @@ impl ThemeBuilder {
 #[serde(default, deny_unknown_fields)]
 pub struct ThemeBuilderSet {
     themes: HashMap<String, ThemeBuilder>,
+    images: HashMap<String, ImageDefinition>,
+
+    /// Texture IDs mapped to the path of a PNG file to load
+    textures: HashMap<String, String>,
+
+    fonts: HashMap<String, FontDefinition>,
+
+    /// The directory that texture and font paths are relative to
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+/// A font file to load, either just the path or the path and a size.  Files
+/// with a `ttf` or `otf` extension are loaded as scalable fonts with `size` as
+/// the height in pixels, while any other file is loaded as a BMFont descriptor.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum FontDefinition {
+    File(String),
+    Sized { file: String, size: f32 },
+}
+
+impl FontDefinition {
+    fn load(&self, dir: &Path) -> Result<Rc<dyn Font>, Error> {
+        let (file, size) = match self {
+            FontDefinition::File(file) => (file, DEFAULT_FONT_SIZE),
+            FontDefinition::Sized { file, size } => (file, *size),
+        };
+
+        let file = dir.join(file);
+        let extension = file.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("ttf") | Some("otf") => Ok(Rc::new(load_truetype_font(file, size)?)),
+            _ => Ok(Rc::new(load_bitmap_font(file)?)),
+        }
+    }
 }
 
 const MAX_FROM_DEPTH: u32 = 10;
 
 impl ThemeBuilderSet {
+    /// Reads the theme file at `path`.  Texture and font paths in the file are
+    /// relative to the directory containing it.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ThemeBuilderSet, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| {
+            Error::new(err.kind(), format!("{}: {}", path.display(), err))
+        })?;
+        let mut set: ThemeBuilderSet = serde_yaml::from_reader(file).map_err(|err| {
+            Error::new(ErrorKind::InvalidInput, format!("{}: {}", path.display(), err))
+        })?;
+        set.dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        Ok(set)
+    }
+
     pub fn create_theme_set(mut self) -> Result<ThemeSet, Error> {
         self.flatten_children()?;
         self.expand_from()?;
@@ -201,7 +299,17 @@ impl ThemeBuilderSet {
         // the default theme in the default location to look
         out.insert(DEFAULT_THEME_ID.to_string(), Theme::default());
 
-        Ok(ThemeSet::new(out))
+        let mut textures = HashMap::new();
+        for (id, path) in self.textures {
+            textures.insert(id, Rc::new(load_texture(self.dir.join(path))?));
+        }
+
+        let mut fonts: HashMap<String, Rc<dyn Font>> = HashMap::new();
+        for (id, definition) in self.fonts {
+            fonts.insert(id, definition.load(&self.dir)?);
+        }
+
+        Ok(ThemeSet::new(out, self.images, textures, fonts))
     }
 
     fn flatten_children(&mut self) -> Result<(), Error> {
@@ -216,7 +324,7 @@ impl ThemeBuilderSet {
 
     fn flatten_children_recursive(&mut self, id: String) -> Result<(), Error> {
         let children: Vec<(String, ThemeBuilder)> =
-            self.themes.get_mut(&id).unwrap().children.drain().collect();
+            self.themes.get_mut(&id).unwrap().children.drain(..).collect();
 
         for (child_id, mut child) in children {
             let new_id = format!("{}.{}", id, child_id);
@@ -269,7 +377,7 @@ impl ThemeBuilderSet {
             // reference at the same time, but as long as they aren't equal
             // it should be ok
             let from_theme = self.themes[&from].clone();
-            ThemeBuilderSet::expand_from_theme(&mut self.themes.get_mut(id).unwrap(), &from_theme);
+            ThemeBuilderSet::expand_from_theme(self.themes.get_mut(id).unwrap(), &from_theme);
         }
         Ok(())
     }
@@ -277,11 +385,15 @@ impl ThemeBuilderSet {
     fn expand_from_theme(to: &mut ThemeBuilder, from: &ThemeBuilder) {
         to.layout = to.layout.or(from.layout);
         to.layout_spacing = to.layout_spacing.or(from.layout_spacing);
+        to.grid_cell = to.grid_cell.or(from.grid_cell);
         to.border = to.border.or(from.border);
         to.size = to.size.or(from.size);
+        to.size_limits = to.size_limits.or(from.size_limits);
+        to.flex = to.flex.or(from.flex);
         to.position = to.position.or(from.position);
         to.kind = to.kind.or(from.kind);
 
+        if to.grid.is_none() { to.grid = from.grid.clone(); }
         if to.text.is_none() { to.text = from.text.clone(); }
         if to.background.is_none() { to.background = from.background.clone(); }
         if to.foreground.is_none() { to.foreground = from.foreground.clone(); }
@@ -0,0 +1,441 @@
+//  This file is part of thrust-ui, the themable UI tooklit written in Rust.
+//  Copyright 2018/2019 Jared Stephen
+//
+//  thrust-ui is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  thrust-ui is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with thrust-ui.  If not, see <http://www.gnu.org/licenses/>
+
+//! Scalable TrueType and OpenType fonts.  Glyphs are rasterized on demand at
+//! the size they are drawn at, and cached in a glyph atlas texture which grows
+//! as needed.
+
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use ab_glyph::{Font as _, FontVec, GlyphId, PxScale, ScaleFont, point};
+use log::warn;
+
+use crate::color::Color;
+use crate::font::Font;
+use crate::render::{Renderer, Texture, UvRect};
+use crate::widget::Rect;
+
+pub const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+const INITIAL_ATLAS_SIZE: u32 = 256;
+const MAX_ATLAS_SIZE: u32 = 4096;
+const GLYPH_PADDING: u32 = 1;
+
+// glyph sizes are cached in steps of 1/64 of a pixel
+const SIZE_STEPS: f32 = 64.0;
+
+pub struct TrueTypeFont {
+    font: FontVec,
+    size: f32,
+    cache: RefCell<GlyphCache>,
+}
+
+impl TrueTypeFont {
+    /// Creates a font from the contents of a TTF or OTF file.  `size` is the
+    /// height in pixels of the font at a `TextParams` scale of `1.0`.
+    pub fn new(data: Vec<u8>, size: f32) -> Result<TrueTypeFont, Error> {
+        let font = FontVec::try_from_vec(data)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))?;
+
+        Ok(TrueTypeFont {
+            font,
+            size,
+            cache: RefCell::new(GlyphCache::new()),
+        })
+    }
+
+    pub fn size(&self) -> f32 { self.size }
+
+    /// The atlas texture containing all glyphs rasterized so far.  The texture
+    /// keeps the same ID as it is updated, see `Texture::version`.
+    pub fn atlas_texture(&self) -> Ref<'_, Texture> {
+        // glyphs are only added while drawing, which always updates the texture
+        Ref::map(self.cache.borrow(), |cache| &cache.atlas.texture)
+    }
+
+    fn scaled(&self) -> ab_glyph::PxScaleFont<&FontVec> {
+        self.font.as_scaled(PxScale::from(self.size))
+    }
+
+    fn draw_glyphs(&self, renderer: &mut dyn Renderer, glyphs: &[(char, f32)], y: f32,
+                   scale: f32, color: Color) {
+        let size = self.size * scale;
+        let baseline = (y + self.baseline() * scale).round() as i32;
+
+        let mut cache = self.cache.borrow_mut();
+        let cached: Vec<_> = glyphs.iter().filter_map(|(c, x)| {
+            let id = self.font.glyph_id(*c);
+            cache.glyph(&self.font, id, size).map(|glyph| (glyph, *x))
+        }).collect();
+
+        // all glyphs are rasterized before the texture is updated, so there is one new version
+        let texture = cache.atlas.texture();
+        for (glyph, x) in cached {
+            if glyph.region.is_empty() { continue; }
+
+            let rect = Rect {
+                x: x.round() as i32 + glyph.offset_x,
+                y: baseline + glyph.offset_y,
+                width: glyph.region.width,
+                height: glyph.region.height,
+            };
+            let uv = UvRect::from_pixels(glyph.region, texture.width(), texture.height());
+            renderer.draw_quad(texture, rect, uv, color);
+        }
+    }
+}
+
+impl Font for TrueTypeFont {
+    fn line_height(&self) -> f32 {
+        let scaled = self.scaled();
+        scaled.height() + scaled.line_gap()
+    }
+
+    fn baseline(&self) -> f32 { self.scaled().ascent() }
+
+    fn advance(&self, c: char) -> f32 {
+        let scaled = self.scaled();
+        scaled.h_advance(scaled.glyph_id(c))
+    }
+
+    fn kerning(&self, left: char, right: char) -> f32 {
+        let scaled = self.scaled();
+        scaled.kern(scaled.glyph_id(left), scaled.glyph_id(right))
+    }
+
+    fn draw_glyph(&self, renderer: &mut dyn Renderer, c: char, x: f32, y: f32,
+                  scale: f32, color: Color) {
+        self.draw_glyphs(renderer, &[(c, x)], y, scale, color);
+    }
+
+    fn draw_line(&self, renderer: &mut dyn Renderer, text: &str, x: f32, y: f32,
+                 scale: f32, color: Color) {
+        let mut glyphs = Vec::new();
+        let mut pen = x;
+        let mut prev = None;
+        for c in text.chars() {
+            if let Some(prev) = prev {
+                pen += self.kerning(prev, c) * scale;
+            }
+            glyphs.push((c, pen));
+            pen += self.advance(c) * scale;
+            prev = Some(c);
+        }
+
+        self.draw_glyphs(renderer, &glyphs, y, scale, color);
+    }
+}
+
+/// Loads the TTF or OTF file at `path`.  `size` is the height in pixels of the
+/// font at a `TextParams` scale of `1.0`.
+pub fn load_truetype_font<P: AsRef<Path>>(path: P, size: f32) -> Result<TrueTypeFont, Error> {
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(|err| {
+        Error::new(err.kind(), format!("{}: {}", path.display(), err))
+    })?;
+
+    TrueTypeFont::new(data, size).map_err(|err| {
+        Error::new(err.kind(), format!("{}: {}", path.display(), err))
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedGlyph {
+    region: Rect,
+
+    // offset of the top left of the glyph from the pen position on the baseline
+    offset_x: i32,
+    offset_y: i32,
+}
+
+struct GlyphCache {
+    glyphs: HashMap<(GlyphId, u32), CachedGlyph>,
+    atlas: GlyphAtlas,
+}
+
+impl GlyphCache {
+    fn new() -> GlyphCache {
+        GlyphCache {
+            glyphs: HashMap::new(),
+            atlas: GlyphAtlas::new(INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE),
+        }
+    }
+
+    fn glyph(&mut self, font: &FontVec, id: GlyphId, size: f32) -> Option<CachedGlyph> {
+        let size_key = (size * SIZE_STEPS).round() as u32;
+        if let Some(glyph) = self.glyphs.get(&(id, size_key)) {
+            return Some(*glyph);
+        }
+
+        let size = size_key as f32 / SIZE_STEPS;
+        let glyph = id.with_scale_and_position(PxScale::from(size), point(0.0, 0.0));
+        let cached = match font.outline_glyph(glyph) {
+            // glyphs such as spaces have no outline
+            None => CachedGlyph { region: Rect::default(), offset_x: 0, offset_y: 0 },
+            Some(outline) => {
+                let bounds = outline.px_bounds();
+                let width = bounds.width() as u32;
+                let height = bounds.height() as u32;
+                let (x, y) = match self.atlas.allocate(width, height) {
+                    None => {
+                        warn!("Glyph atlas is full, unable to cache glyph {:?} at size {}",
+                              id, size);
+                        return None;
+                    },
+                    Some(position) => position,
+                };
+
+                let atlas = &mut self.atlas;
+                outline.draw(|gx, gy, coverage| {
+                    atlas.set_alpha(x + gx, y + gy, (coverage.clamp(0.0, 1.0) * 255.0) as u8);
+                });
+
+                let region = Rect { x: x as i32, y: y as i32, width, height };
+                atlas.mark_dirty(region);
+                CachedGlyph {
+                    region,
+                    offset_x: bounds.min.x as i32,
+                    offset_y: bounds.min.y as i32,
+                }
+            }
+        };
+
+        self.glyphs.insert((id, size_key), cached);
+        Some(cached)
+    }
+}
+
+/// A texture that glyphs are packed into in rows, growing up to `MAX_ATLAS_SIZE`
+/// when full.  Glyphs are stored as white with the coverage in the alpha channel.
+struct GlyphAtlas {
+    width: u32,
+    height: u32,
+    texture: Texture,
+    rows: Vec<AtlasRow>,
+
+    // the area written to since the texture version was last incremented
+    dirty: Option<Rect>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AtlasRow {
+    y: u32,
+    height: u32,
+
+    // the start of the free space at the end of this row
+    x: u32,
+}
+
+impl GlyphAtlas {
+    fn new(width: u32, height: u32) -> GlyphAtlas {
+        GlyphAtlas {
+            width,
+            height,
+            texture: Texture::new(width, height, vec![0; width as usize * height as usize * 4]),
+            rows: Vec::new(),
+            dirty: None,
+        }
+    }
+
+    /// Reserves space for a glyph of the specified size, returning its top left
+    /// position.  Returns `None` if the atlas is full and can't grow any further.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let width = width + GLYPH_PADDING;
+        let height = height + GLYPH_PADDING;
+        if width > MAX_ATLAS_SIZE || height > MAX_ATLAS_SIZE { return None; }
+
+        loop {
+            if let Some(position) = self.find_space(width, height) {
+                return Some(position);
+            }
+
+            // prefer to grow the height, as added width is shared by every row
+            let can_grow_width = self.width < MAX_ATLAS_SIZE;
+            let can_grow_height = self.height < MAX_ATLAS_SIZE;
+            if can_grow_width && (width > self.width || !can_grow_height) {
+                self.grow(self.width * 2, self.height);
+            } else if can_grow_height {
+                self.grow(self.width, self.height * 2);
+            } else {
+                return None;
+            }
+        }
+    }
+
+    fn find_space(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let last = self.rows.len().saturating_sub(1);
+        for (index, row) in self.rows.iter_mut().enumerate() {
+            if row.x + width > self.width { continue; }
+
+            // only the last row may get taller to fit the glyph
+            if height > row.height {
+                if index != last || row.y + height > self.height { continue; }
+                row.height = height;
+            }
+
+            let position = (row.x, row.y);
+            row.x += width;
+            return Some(position);
+        }
+
+        let y = self.rows.last().map_or(0, |row| row.y + row.height);
+        if width > self.width || y + height > self.height { return None; }
+
+        self.rows.push(AtlasRow { y, height, x: width });
+        Some((0, y))
+    }
+
+    fn grow(&mut self, width: u32, height: u32) {
+        let width = width.min(MAX_ATLAS_SIZE);
+        let height = height.min(MAX_ATLAS_SIZE);
+
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        let old_stride = self.width as usize * 4;
+        let stride = width as usize * 4;
+        for (row, old_row) in self.texture.pixels().chunks(old_stride).enumerate() {
+            pixels[row * stride..row * stride + old_stride].copy_from_slice(old_row);
+        }
+
+        self.width = width;
+        self.height = height;
+        self.texture.replace(width, height, pixels);
+        self.dirty = None;
+    }
+
+    fn set_alpha(&mut self, x: u32, y: u32, alpha: u8) {
+        if x >= self.width || y >= self.height { return; }
+
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        self.texture.pixels_mut()[index..index + 4].copy_from_slice(&[255, 255, 255, alpha]);
+    }
+
+    fn mark_dirty(&mut self, region: Rect) {
+        self.dirty = Some(match self.dirty {
+            None => region,
+            Some(dirty) => dirty.union(&region),
+        });
+    }
+
+    /// Returns the texture, first starting a new version of it if any glyphs
+    /// have been written since the last call.
+    fn texture(&mut self) -> &Texture {
+        if let Some(dirty) = self.dirty.take() {
+            self.texture.mark_changed(dirty);
+        }
+        &self.texture
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::software_renderer::SoftwareRenderer;
+
+    // boxes.ttf has 1000 units per em, an ascent of 800, a descent of -200 and
+    // a line gap of 100.  'A' is a 400 x 700 box with an advance of 600, 'B' a
+    // 300 x 700 box with an advance of 400, and a space has an advance of 250.
+    fn boxes() -> TrueTypeFont {
+        let file = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fonts/boxes.ttf");
+        load_truetype_font(file, 20.0).unwrap()
+    }
+
+    #[test]
+    fn metrics_are_scaled_to_the_font_size() {
+        let font = boxes();
+        assert_eq!(font.line_height(), 22.0);
+        assert_eq!(font.baseline(), 16.0);
+        assert_eq!((font.advance('A'), font.advance('B'), font.advance(' ')), (12.0, 8.0, 5.0));
+
+        assert_eq!(font.line_width("AB A", 1.0), 37.0);
+        assert_eq!(font.line_width("AB A", 2.0), 74.0);
+        let size = font.measure("AB\nA", 1.0);
+        assert_eq!((size.width, size.height), (20, 44));
+    }
+
+    #[test]
+    fn glyphs_are_rasterized_into_the_atlas() {
+        let font = boxes();
+        let white = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+        let mut renderer = SoftwareRenderer::new(30, 20);
+        font.draw_line(&mut renderer, "AB", 0.0, 0.0, 1.0, white);
+
+        // 'A' covers x 2 to 10 and 'B' x 13 to 19, from y 2 down to the baseline
+        let covered = |x, y| renderer.pixel(x, y)[3] == 255;
+        assert!(covered(2, 2) && covered(9, 15) && covered(13, 2) && covered(18, 15));
+        assert!(!covered(1, 8) && !covered(10, 8) && !covered(12, 8) && !covered(19, 8));
+        assert!(!covered(5, 1) && !covered(5, 16));
+
+        let (id, version) = (font.atlas_texture().id(), font.atlas_texture().version());
+        font.draw_line(&mut renderer, "BA", 0.0, 0.0, 1.0, white);
+        assert_eq!(font.atlas_texture().version(), version);
+
+        font.draw_line(&mut renderer, "A", 0.0, 0.0, 2.0, white);
+        let texture = font.atlas_texture();
+        assert_eq!((texture.id(), texture.version()), (id, version + 1));
+        let changed = texture.changed_region();
+        assert_eq!((changed.width, changed.height), (16, 28));
+    }
+
+    #[test]
+    fn atlas_updates_its_texture_in_place() {
+        let mut atlas = GlyphAtlas::new(INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE);
+        let id = atlas.texture().id();
+        assert_eq!(atlas.texture().version(), 0);
+
+        atlas.mark_dirty(Rect { x: 0, y: 0, width: 4, height: 4 });
+        atlas.mark_dirty(Rect { x: 10, y: 2, width: 4, height: 4 });
+        let texture = atlas.texture();
+        assert_eq!((texture.id(), texture.version()), (id, 1));
+        let changed = texture.changed_region();
+        assert_eq!((changed.x, changed.y, changed.width, changed.height), (0, 0, 14, 6));
+
+        // grows twice to fit the glyph and its padding
+        atlas.allocate(INITIAL_ATLAS_SIZE * 2, 1).unwrap();
+        let texture = atlas.texture();
+        assert_eq!((texture.id(), texture.version()), (id, 3));
+        assert_eq!(texture.width(), INITIAL_ATLAS_SIZE * 4);
+        assert_eq!(texture.changed_region().width, INITIAL_ATLAS_SIZE * 4);
+    }
+
+    #[test]
+    fn atlas_fills_up_to_max_size() {
+        let mut atlas = GlyphAtlas::new(INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE);
+
+        // each glyph takes up 512 x 512 including padding, so 8 x 8 fit at the max size
+        let glyph_size = 512 - GLYPH_PADDING;
+        let mut count = 0;
+        while atlas.allocate(glyph_size, glyph_size).is_some() {
+            count += 1;
+            assert!(count <= 64, "atlas allocated more glyphs than fit");
+        }
+
+        assert_eq!(count, 64);
+        assert_eq!(atlas.width, MAX_ATLAS_SIZE);
+        assert_eq!(atlas.height, MAX_ATLAS_SIZE);
+        assert!(atlas.allocate(1, 1).is_none());
+    }
+
+    #[test]
+    fn atlas_rejects_glyphs_larger_than_max_size() {
+        let mut atlas = GlyphAtlas::new(INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE);
+        assert!(atlas.allocate(MAX_ATLAS_SIZE, 1).is_none());
+        assert_eq!(atlas.allocate(10, 10), Some((0, 0)));
+    }
+}
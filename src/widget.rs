@@ -20,17 +20,21 @@ use std::rc::Rc;
 use serde_derive::Deserialize;
 
 use crate::widget_tree::WidgetTree;
-use crate::theme::DEFAULT_THEME_ID;
-use crate::image::Image;
-use crate::input::{Cursor, MouseButton};
+use crate::theme::{Border, TextParams, DEFAULT_THEME_ID};
+use crate::font::{EmptyFont, Font, FontRegistry};
+use crate::image::{Image, ImageRegistry};
+use crate::input::{Cursor, Event, Key, Modifiers, MouseButton, ScrollUnit};
+use crate::render::Renderer;
+
+pub type CallbackFn<T> = Rc<dyn Fn(&mut WidgetTree, usize, T) -> bool>;
 
 #[derive(Clone)]
 pub struct Callback<T> {
-    callback: Rc<Fn(&mut WidgetTree, usize, T) -> bool>,
+    callback: CallbackFn<T>,
 }
 
 impl<T> Callback<T> {
-    pub fn new(cb: Rc<Fn(&mut WidgetTree, usize, T) -> bool>) -> Callback<T> {
+    pub fn new(cb: CallbackFn<T>) -> Callback<T> {
         Callback {
             callback: cb
         }
@@ -42,22 +46,23 @@ impl<T> Callback<T> {
 }
 
 impl<T> Default for Callback<T> {
+    // unhandled, so events propagate on to the next widget
     fn default() -> Callback<T> {
         Callback {
-            callback: Rc::new(|_, _, _| true),
+            callback: Rc::new(|_, _, _| false),
         }
     }
 }
 
-pub trait Renderer {
-    fn render(&mut self);
-}
-
 pub struct WidgetState {
     pub(crate) theme_full_id: String,
     pub(crate) theme_partial_id: String,
     position: Point,
     size: Size,
+    border: Border,
+    text: Option<String>,
+    text_params: TextParams,
+    font: Rc<dyn Font>,
     background: Image,
     foreground: Image,
 
@@ -66,9 +71,19 @@ pub struct WidgetState {
     pub(crate) mouse_moved_callback: Callback<(f32, f32)>,
     pub(crate) mouse_entered_callback: Callback<()>,
     pub(crate) mouse_exited_callback: Callback<()>,
-
+    pub(crate) mouse_scrolled_callback: Callback<(f32, f32, ScrollUnit)>,
+    pub(crate) key_pressed_callback: Callback<(Key, Modifiers)>,
+    pub(crate) key_released_callback: Callback<(Key, Modifiers)>,
+    pub(crate) text_input_callback: Callback<String>,
+    pub(crate) focus_gained_callback: Callback<()>,
+    pub(crate) focus_lost_callback: Callback<()>,
+    pub(crate) capture_callback: Callback<Event>,
+
+    focusable: bool,
+    visible: bool,
     pub(crate) index: usize,
     pub(crate) to_add: Vec<Box<dyn Widget>>,
+    pub(crate) layout_dirty: bool,
 }
 
 impl Default for WidgetState {
@@ -78,6 +93,10 @@ impl Default for WidgetState {
             theme_partial_id: DEFAULT_THEME_ID.to_string(),
             position: Point::default(),
             size: Size::default(),
+            border: Border::default(),
+            text: None,
+            text_params: TextParams::default(),
+            font: Rc::new(EmptyFont { }),
             background: Image::default(),
             foreground: Image::default(),
 
@@ -86,39 +105,158 @@ impl Default for WidgetState {
             mouse_moved_callback: Callback::default(),
             mouse_entered_callback: Callback::default(),
             mouse_exited_callback: Callback::default(),
+            mouse_scrolled_callback: Callback::default(),
+            key_pressed_callback: Callback::default(),
+            key_released_callback: Callback::default(),
+            text_input_callback: Callback::default(),
+            focus_gained_callback: Callback::default(),
+            focus_lost_callback: Callback::default(),
+            capture_callback: Callback::default(),
+            focusable: false,
+            visible: true,
             index: 0,
             to_add: Vec::default(),
+            layout_dirty: true,
         }
     }
 }
 
 impl WidgetState {
-    pub fn set_mouse_pressed_callback(&mut self, callback:
-                                      Rc<Fn(&mut WidgetTree, usize, MouseButton) -> bool>) {
+    pub fn set_mouse_pressed_callback(&mut self, callback: CallbackFn<MouseButton>) {
         self.mouse_pressed_callback = Callback::new(callback);
     }
 
-    pub fn set_mouse_released_callback(&mut self, callback:
-                                       Rc<Fn(&mut WidgetTree, usize, MouseButton) -> bool>) {
+    pub fn set_mouse_released_callback(&mut self, callback: CallbackFn<MouseButton>) {
         self.mouse_released_callback = Callback::new(callback);
     }
 
-    pub fn set_mouse_moved_callback(&mut self, callback:
-                                    Rc<Fn(&mut WidgetTree, usize, (f32, f32)) -> bool>) {
+    pub fn set_mouse_moved_callback(&mut self, callback: CallbackFn<(f32, f32)>) {
         self.mouse_moved_callback = Callback::new(callback);
     }
 
-    pub fn set_mouse_entered_callback(&mut self, callback:
-                                      Rc<Fn(&mut WidgetTree, usize, ()) -> bool>) {
+    pub fn set_mouse_entered_callback(&mut self, callback: CallbackFn<()>) {
         self.mouse_entered_callback = Callback::new(callback);
     }
 
-    pub fn set_mouse_exited_callback(&mut self, callback:
-                                     Rc<Fn(&mut WidgetTree, usize, ()) -> bool>) {
+    pub fn set_mouse_exited_callback(&mut self, callback: CallbackFn<()>) {
         self.mouse_exited_callback = Callback::new(callback);
     }
 
-    pub(crate) fn draw(&self, renderer: &mut Renderer) {
+    /// Sets the callback for scrolling over this widget, which receives the
+    /// horizontal and vertical deltas.  If the callback returns false, the event
+    /// is passed on to the parent of this widget.
+    pub fn set_mouse_scrolled_callback(&mut self,
+                                       callback: CallbackFn<(f32, f32, ScrollUnit)>) {
+        self.mouse_scrolled_callback = Callback::new(callback);
+    }
+
+    pub fn set_key_pressed_callback(&mut self, callback: CallbackFn<(Key, Modifiers)>) {
+        self.key_pressed_callback = Callback::new(callback);
+    }
+
+    pub fn set_key_released_callback(&mut self, callback: CallbackFn<(Key, Modifiers)>) {
+        self.key_released_callback = Callback::new(callback);
+    }
+
+    pub fn set_text_input_callback(&mut self, callback: CallbackFn<String>) {
+        self.text_input_callback = Callback::new(callback);
+    }
+
+    pub fn set_focus_gained_callback(&mut self, callback: CallbackFn<()>) {
+        self.focus_gained_callback = Callback::new(callback);
+    }
+
+    pub fn set_focus_lost_callback(&mut self, callback: CallbackFn<()>) {
+        self.focus_lost_callback = Callback::new(callback);
+    }
+
+    /// Sets the callback fired during the capture phase for events targeting any
+    /// descendant of this widget, before the target itself receives the event.
+    /// Returning true stops the event from propagating any further.  See
+    /// `WidgetTree::handle_event`.
+    pub fn set_capture_callback(&mut self, callback: CallbackFn<Event>) {
+        self.capture_callback = Callback::new(callback);
+    }
+
+    /// Sets whether this widget can receive keyboard focus, either with
+    /// `WidgetTree::set_focus` or by tab navigation
+    pub fn set_focusable(&mut self, focusable: bool) {
+        self.focusable = focusable;
+    }
+
+    pub fn is_focusable(&self) -> bool { self.focusable }
+
+    /// Sets whether this widget is shown.  Hidden widgets and their children
+    /// are not drawn and do not receive mouse events, but are still laid out.
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn is_visible(&self) -> bool { self.visible }
+
+    pub fn set_position(&mut self, position: Point) {
+        self.position = position;
+    }
+
+    pub fn set_size(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    pub(crate) fn set_border(&mut self, border: Border) {
+        self.border = border;
+    }
+
+    pub fn border(&self) -> Border { self.border }
+
+    pub(crate) fn set_theme_text(&mut self, text: Option<String>) {
+        self.text = text;
+    }
+
+    /// The `text` specified by the theme, if any
+    pub fn theme_text(&self) -> Option<&str> { self.text.as_deref() }
+
+    pub(crate) fn set_text_params(&mut self, params: TextParams) {
+        self.text_params = params;
+    }
+
+    pub fn text_params(&self) -> &TextParams { &self.text_params }
+
+    pub(crate) fn set_font(&mut self, font: Rc<dyn Font>) {
+        self.font = font;
+    }
+
+    /// The font specified by the theme `text_params`
+    pub fn font(&self) -> &Rc<dyn Font> { &self.font }
+
+    /// Measures `text` with this widget's font and text params
+    pub fn measure_text(&self, text: &str) -> Size {
+        self.font.measure(text, self.text_params.scale)
+    }
+
+    pub(crate) fn set_background(&mut self, image: Image) {
+        self.background = image;
+    }
+
+    pub(crate) fn set_foreground(&mut self, image: Image) {
+        self.foreground = image;
+    }
+
+    /// Marks this widget as needing to be laid out again on the next call to
+    /// `WidgetTree::layout`.  This should be called whenever something that
+    /// affects the widget's size changes.
+    pub fn invalidate_layout(&mut self) {
+        self.layout_dirty = true;
+    }
+
+    pub fn is_layout_dirty(&self) -> bool { self.layout_dirty }
+
+    /// Returns the area of this widget that its children are laid out within,
+    /// which is the widget's bounds with the theme `border` removed.
+    pub fn inner(&self) -> Rect {
+        Rect::new(self.position, self.size).inner(&self.border)
+    }
+
+    pub(crate) fn draw(&self, renderer: &mut dyn Renderer) {
         self.background.draw(renderer, self.position, self.size);
         self.foreground.draw(renderer, self.position, self.size);
     }
@@ -147,8 +285,18 @@ pub trait Widget {
     /// widget tree.
     fn on_remove(&mut self) { }
 
-    /// Called after the widget has been added to the tree and the theme
-    /// has been applied.
+    /// Called during `WidgetTree::layout` after the theme has been applied and
+    /// before `content_size`, to look up any fonts and images this widget needs
+    /// beyond those specified by the theme.
+    fn resolve_resources(&mut self, _fonts: &FontRegistry, _images: &ImageRegistry) { }
+
+    /// Returns the size of any content drawn by this widget itself, such as text.
+    /// Called during `WidgetTree::layout` after the theme has been applied, and
+    /// treated as an extra child when computing `ChildMax` and `ChildSum` sizes.
+    fn content_size(&self) -> Size { Size::default() }
+
+    /// Called during `WidgetTree::layout`, after the position and size of this
+    /// widget and all of its children have been computed from the theme.
     fn layout(&mut self) { }
 
     fn index(&self) -> usize { self.state().index }
@@ -163,7 +311,7 @@ pub trait Widget {
 
     fn size(&self) -> Size { self.state().size }
 
-    fn draw(&self, renderer: &mut Renderer) {
+    fn draw(&self, renderer: &mut dyn Renderer) {
         self.state().draw(renderer);
     }
 
@@ -173,14 +321,15 @@ pub trait Widget {
 
     fn set_full_theme_id(&mut self, id: String) {
         self.state_mut().theme_full_id = id;
+        self.state_mut().invalidate_layout();
     }
 
-    fn as_any(&self) -> &Any;
+    fn as_any(&self) -> &dyn Any;
 
-    fn as_any_mut(&mut self) -> &mut Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
-impl Widget {
+impl dyn Widget {
     pub fn empty() -> impl Widget {
         EmptyWidget::default()
     }
@@ -206,6 +355,57 @@ pub struct Size {
     pub height: u32,
 }
 
+#[derive(Deserialize, Default, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields, default)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(position: Point, size: Size) -> Rect {
+        Rect { x: position.x, y: position.y, width: size.width, height: size.height }
+    }
+
+    pub fn position(&self) -> Point { Point { x: self.x, y: self.y } }
+
+    pub fn size(&self) -> Size { Size { width: self.width, height: self.height } }
+
+    /// Returns the area covered by both this rect and `other`, which may have
+    /// zero width or height if they do not overlap.
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width as i32).min(other.x + other.width as i32);
+        let bottom = (self.y + self.height as i32).min(other.y + other.height as i32);
+        Rect { x, y, width: (right - x).max(0) as u32, height: (bottom - y).max(0) as u32 }
+    }
+
+    /// Returns the smallest rect covering both this rect and `other`.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width as i32).max(other.x + other.width as i32);
+        let bottom = (self.y + self.height as i32).max(other.y + other.height as i32);
+        Rect { x, y, width: (right - x) as u32, height: (bottom - y) as u32 }
+    }
+
+    pub fn is_empty(&self) -> bool { self.width == 0 || self.height == 0 }
+
+    /// Returns this rect shrunk by the specified `border` on each side.  The
+    /// resulting width and height will never be less than zero.
+    pub fn inner(&self, border: &Border) -> Rect {
+        Rect {
+            x: self.x + border.left as i32,
+            y: self.y + border.top as i32,
+            width: self.width.saturating_sub(border.left + border.right),
+            height: self.height.saturating_sub(border.top + border.bottom),
+        }
+    }
+}
+
 
 widget!{
     #[derive(Default)]
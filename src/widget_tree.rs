@@ -16,15 +16,29 @@
 
 use std::ops::{Deref, DerefMut, Index, IndexMut};
 
-use crate::input::{Event};
-use crate::theme::{Theme, ThemeSet, Kind};
-use crate::widget::{Renderer, Widget, EmptyWidget};
+use log::warn;
+
+use crate::font::FontRegistry;
+use crate::image::ImageRegistry;
+use crate::input::{Cursor, Event, EventContext, EventPhase, Key};
+use crate::theme::{Theme, ThemeSet, Kind, LayoutKind};
+use crate::render::Renderer;
+use crate::widget::{Widget, EmptyWidget, Rect, Size};
 use crate::label::Label;
+use crate::layout;
 
 pub struct WidgetTree {
     widgets: Vec<Option<Box<dyn Widget>>>,
     tree: Vec<Option<TreeEntry>>,
     themes: ThemeSet,
+    images: ImageRegistry,
+    fonts: FontRegistry,
+    size: Size,
+    focus: Option<usize>,
+
+    // the widgets under the cursor, from the root down to the deepest widget
+    hovered: Vec<usize>,
+    event_contexts: Vec<EventContext>,
 }
 
 #[derive(Debug)]
@@ -48,11 +62,27 @@ impl IndexMut<usize> for WidgetTree {
 }
 
 impl WidgetTree {
+    /// Creates a new tree with the specified `root` widget.  The textures, images
+    /// and fonts defined in the `themes` are added to the tree's registries.
     pub fn new<T: Widget + 'static>(root: T, themes: ThemeSet) -> WidgetTree {
+        let mut images = ImageRegistry::new();
+        if let Err(err) = images.insert_theme_images(&themes) {
+            warn!("{}", err);
+        }
+
+        let mut fonts = FontRegistry::new();
+        fonts.insert_theme_fonts(&themes);
+
         let mut tree = WidgetTree {
             widgets: Vec::new(),
             tree: Vec::new(),
             themes,
+            images,
+            fonts,
+            size: Size::default(),
+            focus: None,
+            hovered: Vec::new(),
+            event_contexts: Vec::new(),
         };
 
         let mut root = Box::new(root);
@@ -66,13 +96,180 @@ impl WidgetTree {
         self.themes.get(id)
     }
 
+    pub fn themes(&self) -> &ThemeSet { &self.themes }
+
+    pub fn images(&self) -> &ImageRegistry { &self.images }
+
+    /// Replaces the registry used to look up theme images, including the theme
+    /// images added by `new`, invalidating the layout of the whole tree.
+    pub fn set_images(&mut self, images: ImageRegistry) {
+        self.images = images;
+        self[0].state_mut().invalidate_layout();
+    }
+
+    /// Returns the registry used to look up theme `background` and `foreground`
+    /// images.  Images are applied to widgets during `layout`, so the whole tree
+    /// is invalidated when the registry is accessed mutably.
+    pub fn images_mut(&mut self) -> &mut ImageRegistry {
+        self[0].state_mut().invalidate_layout();
+        &mut self.images
+    }
+
+    pub fn fonts(&self) -> &FontRegistry { &self.fonts }
+
+    /// Returns the registry used to look up theme fonts.  Fonts are applied to
+    /// widgets during `layout`, so the whole tree is invalidated when the registry
+    /// is accessed mutably.
+    pub fn fonts_mut(&mut self) -> &mut FontRegistry {
+        self[0].state_mut().invalidate_layout();
+        &mut self.fonts
+    }
+
+    /// Replaces the registry used to look up theme fonts, including the theme
+    /// fonts added by `new`, invalidating the layout of the whole tree.
+    pub fn set_fonts(&mut self, fonts: FontRegistry) {
+        self.fonts = fonts;
+        self[0].state_mut().invalidate_layout();
+    }
+
+    /// Returns the overall size of the area that the root widget is laid out within.
+    pub fn size(&self) -> Size { self.size }
+
+    /// Sets the overall size of the area that the root widget is laid out within,
+    /// typically the size of the screen or window.  Takes effect on the next `layout`.
+    pub fn set_size(&mut self, size: Size) {
+        self.size = size;
+        self[0].state_mut().invalidate_layout();
+    }
+
+    /// Computes the position and size of widgets in the tree from their themes,
+    /// and then calls `Widget::layout` on each widget that was laid out.  Only
+    /// widgets whose layout has been invalidated are laid out again, along with
+    /// any ancestors whose size or child arrangement depends on them.  Within each
+    /// such subtree, sizes that do not depend on the parent are measured bottom up
+    /// first, then widgets are placed top down within the inner area of their parent.
+    pub fn layout(&mut self) {
+        let mut roots: Vec<usize> = Vec::new();
+        for index in 0..self.widgets.len() {
+            match &self.widgets[index] {
+                Some(widget) if widget.state().is_layout_dirty() => (),
+                _ => continue,
+            }
+
+            let root = self.layout_root(index);
+            if !roots.contains(&root) { roots.push(root); }
+        }
+
+        // subtrees inside another root will be laid out as part of that root
+        let roots: Vec<usize> = roots.iter().cloned()
+            .filter(|root| !roots.iter().any(|other| self.is_ancestor(*other, *root)))
+            .collect();
+
+        for root in roots {
+            self.layout_subtree(root);
+        }
+    }
+
+    // Finds the widget that must be laid out again when the widget at `index`
+    // has changed.  Box and grid parents need to rearrange all of their children,
+    // and parents sized by their children need to be placed again themselves.
+    fn layout_root(&self, index: usize) -> usize {
+        let mut root = index;
+        while root != 0 {
+            let parent = self.tree(root).parent;
+            let theme = self.themes.get(self[parent].theme_id());
+            if layout::depends_on_children(theme) {
+                root = parent;
+            } else {
+                if theme.layout != LayoutKind::Normal { root = parent; }
+                break;
+            }
+        }
+        root
+    }
+
+    fn is_ancestor(&self, ancestor: usize, index: usize) -> bool {
+        let mut index = index;
+        while index != 0 {
+            index = self.tree(index).parent;
+            if index == ancestor { return true; }
+        }
+        false
+    }
+
+    fn layout_subtree(&mut self, index: usize) {
+        let current = Rect::new(self[index].position(), self[index].size());
+        self.measure(index);
+
+        let widget = &self[index];
+        let theme = self.themes.get(widget.theme_id());
+        let rect = if index == 0 {
+            layout::place(theme, widget.size(), Rect::new(Default::default(), self.size))
+        } else {
+            let parent = self.tree(index).parent;
+            let parent_theme = self.themes.get(self[parent].theme_id());
+            if parent_theme.layout == LayoutKind::Normal {
+                layout::place(theme, widget.size(), self[parent].state().inner())
+            } else {
+                // the parent arrangement is unaffected, so keep the same bounds
+                current
+            }
+        };
+
+        self.place(index, rect);
+    }
+
+    fn measure(&mut self, index: usize) -> Size {
+        let children = self.tree(index).children.clone();
+        let child_sizes: Vec<Size> = children.into_iter().map(|child| self.measure(child)).collect();
+
+        let widget = self.widgets[index].as_mut().unwrap();
+        let theme = self.themes.get(widget.theme_id());
+
+        let state = widget.state_mut();
+        state.set_border(theme.border);
+        state.set_theme_text(theme.text.clone());
+        state.set_text_params(theme.text_params.clone());
+        state.set_font(self.fonts.font(&theme.text_params.font));
+        state.set_background(self.images.image(theme.background.as_deref()));
+        state.set_foreground(self.images.image(theme.foreground.as_deref()));
+
+        widget.resolve_resources(&self.fonts, &self.images);
+        let size = layout::measure(theme, &child_sizes, widget.content_size());
+        widget.state_mut().set_size(size);
+        size
+    }
+
+    fn place(&mut self, index: usize, rect: Rect) {
+        let state = self.widgets[index].as_mut().unwrap().state_mut();
+        state.set_position(rect.position());
+        state.set_size(rect.size());
+        state.layout_dirty = false;
+        let inner = state.inner();
+
+        let children = self.tree(index).children.clone();
+        let rects = {
+            let items: Vec<_> = children.iter().map(|child| {
+                let widget = &self[*child];
+                (self.themes.get(widget.theme_id()), widget.size())
+            }).collect();
+            layout::arrange(self.themes.get(self[index].theme_id()), inner, &items)
+        };
+
+        for (child, rect) in children.into_iter().zip(rects) {
+            self.place(child, rect);
+        }
+
+        self[index].layout();
+    }
+
     /// Traverses the widget tree up, starting from the parent of `index` looking for a widget
     /// with the specified concrete type `T`, returning the first one found.  If no such
     /// widget is found, panics.  Panics if `index` is invalid
-    pub fn parent_mut<'a, T: Widget + 'static>(&'a mut self, index: usize) -> &'a mut T {
+    pub fn parent_mut<T: Widget + 'static>(&mut self, index: usize) -> &mut T {
         let mut index = self.tree(index).parent;
         loop {
-            if let Some(_) = self[index].as_any_mut().downcast_mut::<T>() {
+            if self[index].as_any_mut().downcast_mut::<T>().is_some() {
                 break;
             }
 
@@ -144,30 +341,240 @@ impl WidgetTree {
                 Some(event) => event,
             };
 
-            self.dispatch_event(&event, 0);
+            self.handle_event(event);
         }
     }
 
+    /// Sends the `event` to the appropriate widgets.  Each event has a target
+    /// widget: the topmost widget under the cursor for mouse events, or the
+    /// focused widget for keyboard and text events.  If there is no focused
+    /// widget, keyboard and text events target the root, while mouse events
+    /// outside the root are not sent to any widget.
+    ///
+    /// Events are propagated in three phases.  In the capture phase, the capture
+    /// callbacks of the target's ancestors are fired from the root down.  Then
+    /// the event callback of the target itself is fired, followed by the bubble
+    /// phase where the event callbacks of the ancestors are fired back up to the
+    /// root.  Propagation stops when a callback returns true, or calls
+    /// `EventContext::stop_propagation` on the `event_context` of the tree.
+    /// Callbacks that have not been set never stop propagation.
+    ///
+    /// `MouseMoved` events also fire the mouse exited and entered callbacks on
+    /// widgets that are no longer or are newly under the cursor.
+    ///
+    /// By default, pressing Tab or Shift-Tab moves the focus to the next or
+    /// previous focusable widget once the event has been propagated.  A callback
+    /// may call `EventContext::prevent_default` to stop this.
     pub fn handle_event(&mut self, event: Event) {
-        self.dispatch_event(&event, 0);
-    }
+        use crate::input::EventKind::*;
+        let target = match &event.kind {
+            KeyPressed { .. } | KeyReleased { .. } | TextInput { .. } => {
+                Some(self.focus.unwrap_or(0))
+            },
+            MouseMoved { .. } => {
+                self.update_hover(&event.cursor);
+                self.hovered()
+            },
+            MousePressed { .. } | MouseReleased { .. } | MouseScrolled { .. } => {
+                self.widget_at(&event.cursor)
+            },
+        };
 
-    fn dispatch_event(&mut self, event: &Event, index: usize) -> bool {
-        if self[index].state().is_inside(&event.cursor) {
-            let len = self.tree(index).children.len();
-            for i in 0..len {
-                let child_index = self.tree(index).children[i];
-                if self.dispatch_event(event, child_index) {
-                    return true;
+        let context = match target {
+            None => return,
+            Some(target) => self.propagate(&event, target),
+        };
+
+        if context.is_default_prevented() { return; }
+
+        if let KeyPressed { key: Key::Tab, modifiers } = &event.kind {
+            if !modifiers.control && !modifiers.alt && !modifiers.logo {
+                if modifiers.shift {
+                    self.focus_previous();
+                } else {
+                    self.focus_next();
                 }
             }
+        }
+    }
+
+    /// Returns the context of the event currently being propagated, if any.
+    /// Callbacks can use this to find the phase and target of the event, and
+    /// to stop propagation or prevent the default action.
+    pub fn event_context(&self) -> Option<&EventContext> {
+        self.event_contexts.last()
+    }
 
-            if self.fire_event(index, event) {
-                return true;
+    pub fn event_context_mut(&mut self) -> Option<&mut EventContext> {
+        self.event_contexts.last_mut()
+    }
+
+    fn propagate(&mut self, event: &Event, target: usize) -> EventContext {
+        let mut path = vec![target];
+        while *path.last().unwrap() != 0 {
+            path.push(self.tree(*path.last().unwrap()).parent);
+        }
+        path.reverse();
+        let ancestors = &path[..path.len() - 1];
+
+        // a stack, as callbacks may send further events to the tree
+        self.event_contexts.push(EventContext::new(target));
+
+        for index in ancestors.iter() {
+            if self.fire_phase(EventPhase::Capture, *index, event) { break; }
+        }
+
+        if !self.event_context().unwrap().is_propagation_stopped() {
+            self.fire_phase(EventPhase::Target, target, event);
+        }
+
+        for index in ancestors.iter().rev() {
+            if self.event_context().unwrap().is_propagation_stopped() { break; }
+            self.fire_phase(EventPhase::Bubble, *index, event);
+        }
+
+        self.event_contexts.pop().unwrap()
+    }
+
+    // fires the callback for the phase, returning whether propagation is stopped
+    fn fire_phase(&mut self, phase: EventPhase, index: usize, event: &Event) -> bool {
+        // callbacks may remove widgets from the tree
+        if self.widgets[index].is_none() { return false; }
+
+        let context = self.event_context_mut().unwrap();
+        context.phase = phase;
+        context.current = index;
+
+        let handled = match phase {
+            EventPhase::Capture => {
+                let cb = self[index].state().capture_callback.clone();
+                cb.fire(self, index, event.clone())
+            },
+            EventPhase::Target | EventPhase::Bubble => self.fire_event(index, event),
+        };
+
+        let context = self.event_context_mut().unwrap();
+        if handled { context.stop_propagation(); }
+        context.is_propagation_stopped()
+    }
+
+    /// Returns the index of the deepest widget under the cursor as of the last
+    /// `MouseMoved` event, if any
+    pub fn hovered(&self) -> Option<usize> { self.hovered.last().cloned() }
+
+    /// Returns whether the widget at `index` or any of its children were under
+    /// the cursor as of the last `MouseMoved` event
+    pub fn is_hovered(&self, index: usize) -> bool { self.hovered.contains(&index) }
+
+    // fires exited on widgets no longer under the cursor, deepest first, then
+    // entered on widgets newly under the cursor, outermost first
+    fn update_hover(&mut self, cursor: &Cursor) {
+        let hovered = self.hover_chain(cursor);
+        let old = std::mem::replace(&mut self.hovered, hovered.clone());
+
+        // callbacks may remove widgets from the tree
+        for index in old.iter().rev().filter(|index| !hovered.contains(index)) {
+            if self.widgets[*index].is_none() { continue; }
+            let cb = self[*index].state().mouse_exited_callback.clone();
+            cb.fire(self, *index, ());
+        }
+
+        for index in hovered.iter().filter(|index| !old.contains(index)) {
+            if self.widgets[*index].is_none() { continue; }
+            let cb = self[*index].state().mouse_entered_callback.clone();
+            cb.fire(self, *index, ());
+        }
+    }
+
+    fn hover_chain(&self, cursor: &Cursor) -> Vec<usize> {
+        let mut chain = Vec::new();
+        if !self.is_hit(0, cursor) { return chain; }
+
+        let mut index = 0;
+        chain.push(index);
+        while let Some(child) = self.child_at(index, cursor) {
+            index = child;
+            chain.push(index);
+        }
+        chain
+    }
+
+    // finds the topmost visible child of `index` under the cursor
+    fn child_at(&self, index: usize, cursor: &Cursor) -> Option<usize> {
+        self.tree(index).children.iter().rev()
+            .find(|child| self.is_hit(**child, cursor))
+            .cloned()
+    }
+
+    fn is_hit(&self, index: usize, cursor: &Cursor) -> bool {
+        let state = self[index].state();
+        state.is_visible() && state.is_inside(cursor)
+    }
+
+    /// Returns the index of the topmost widget under the `cursor`, if any.  Where
+    /// widgets overlap, later children are drawn on top of earlier ones and of
+    /// their parent, so the deepest, last drawn widget is returned.  Hidden
+    /// widgets are skipped.
+    pub fn widget_at(&self, cursor: &Cursor) -> Option<usize> {
+        self.hover_chain(cursor).last().cloned()
+    }
+
+    /// Returns the index of the widget with keyboard focus, if any
+    pub fn focus(&self) -> Option<usize> { self.focus }
+
+    pub fn has_focus(&self, index: usize) -> bool { self.focus == Some(index) }
+
+    /// Moves the keyboard focus to the widget at `index`, or clears it for `None`,
+    /// firing the focus lost and focus gained callbacks.  Widgets that are not
+    /// focusable are ignored.  Panics if the index is invalid.
+    pub fn set_focus(&mut self, index: Option<usize>) {
+        if let Some(index) = index {
+            self.check_index(index);
+            if !self[index].state().is_focusable() {
+                warn!("Widget {} is not focusable", index);
+                return;
             }
         }
 
-        false
+        if index == self.focus { return; }
+
+        if let Some(old) = self.focus.take() {
+            let cb = self[old].state().focus_lost_callback.clone();
+            cb.fire(self, old, ());
+        }
+
+        self.focus = index;
+        if let Some(new) = index {
+            let cb = self[new].state().focus_gained_callback.clone();
+            cb.fire(self, new, ());
+        }
+    }
+
+    /// Moves the focus to the next focusable widget in tree order, wrapping
+    /// around at the end of the tree
+    pub fn focus_next(&mut self) {
+        let order = self.focus_order();
+        let next = match self.focus.and_then(|focus| order.iter().position(|i| *i == focus)) {
+            None => order.first(),
+            Some(position) => order.get((position + 1) % order.len()),
+        };
+        self.set_focus(next.cloned());
+    }
+
+    /// Moves the focus to the previous focusable widget in tree order, wrapping
+    /// around at the start of the tree
+    pub fn focus_previous(&mut self) {
+        let order = self.focus_order();
+        let previous = match self.focus.and_then(|focus| order.iter().position(|i| *i == focus)) {
+            None => order.last(),
+            Some(position) => order.get((position + order.len() - 1) % order.len()),
+        };
+        self.set_focus(previous.cloned());
+    }
+
+    fn focus_order(&self) -> Vec<usize> {
+        self.iter(0).filter(|widget| widget.state().is_focusable())
+            .map(|widget| widget.index()).collect()
     }
 
     fn fire_event(&mut self, index: usize, event: &Event) -> bool {
@@ -184,7 +591,23 @@ impl WidgetTree {
             MouseReleased { button } => {
                 let cb = self[index].state().mouse_released_callback.clone();
                 cb.fire(self, index, *button)
-            }
+            },
+            MouseScrolled { delta_x, delta_y, unit } => {
+                let cb = self[index].state().mouse_scrolled_callback.clone();
+                cb.fire(self, index, (*delta_x, *delta_y, *unit))
+            },
+            KeyPressed { key, modifiers } => {
+                let cb = self[index].state().key_pressed_callback.clone();
+                cb.fire(self, index, (*key, *modifiers))
+            },
+            KeyReleased { key, modifiers } => {
+                let cb = self[index].state().key_released_callback.clone();
+                cb.fire(self, index, (*key, *modifiers))
+            },
+            TextInput { text } => {
+                let cb = self[index].state().text_input_callback.clone();
+                cb.fire(self, index, text.clone())
+            },
         }
     }
 
@@ -236,6 +659,41 @@ impl WidgetTree {
         self.add_child_known_parent(parent_index, child);
     }
 
+    /// Removes the widget at `index` and all of its children from the tree, calling
+    /// `on_remove` on each of them.  Panics if the index is invalid or is the root.
+    pub fn remove(&mut self, index: usize) {
+        self.check_index(index);
+        if index == 0 {
+            panic!("Unable to remove the root widget from the tree");
+        }
+
+        if let Some(focus) = self.focus {
+            if focus == index || self.is_ancestor(index, focus) {
+                self.set_focus(None);
+            }
+        }
+
+        let parent = self.tree(index).parent;
+        self.tree[parent].as_mut().unwrap().children.retain(|child| *child != index);
+        self[parent].state_mut().invalidate_layout();
+
+        self.remove_recursive(index);
+
+        let widgets = &self.widgets;
+        self.hovered.retain(|index| widgets[*index].is_some());
+    }
+
+    fn remove_recursive(&mut self, index: usize) {
+        let entry = self.tree[index].take().unwrap();
+        for child in entry.children {
+            self.remove_recursive(child);
+        }
+
+        if let Some(mut widget) = self.widgets[index].take() {
+            widget.on_remove();
+        }
+    }
+
     fn add_child_known_parent(&mut self, parent_index: usize, mut child: Box<dyn Widget>) {
         let child_index = self.widgets.len();
         child.state_mut().index = child_index;
@@ -268,24 +726,39 @@ impl WidgetTree {
             let theme = self.themes.get(&child_id);
             match theme.kind {
                 Kind::Label => {
-                    let mut label = Label::new("".to_string());
+                    let mut label = Label::default();
                     label.set_theme(theme.id.deref());
-                    self.add_child_known_parent(parent_index, Box::new(label));
+                    self.add_child_known_parent(child_index, Box::new(label));
                 },
                 Kind::Container => {
                     let mut widget = EmptyWidget::new();
                     widget.set_theme(theme.id.deref());
-                    self.add_child_known_parent(parent_index, Box::new(widget));
+                    self.add_child_known_parent(child_index, Box::new(widget));
                 },
                 Kind::Ref => (),
             }
         }
     }
 
-    pub fn draw(&self, renderer: &mut Renderer) {
-        for widget in self.iter(0) {
-            widget.draw(renderer);
+    /// Draws all visible widgets in drawing order, see `iter`.  The children of
+    /// each widget are clipped to that widget's bounds.
+    pub fn draw(&self, renderer: &mut dyn Renderer) {
+        self.draw_widget(0, renderer);
+    }
+
+    fn draw_widget(&self, index: usize, renderer: &mut dyn Renderer) {
+        let widget = &self[index];
+        if !widget.state().is_visible() { return; }
+        widget.draw(renderer);
+
+        let children = &self.tree(index).children;
+        if children.is_empty() { return; }
+
+        renderer.push_clip(Rect::new(widget.position(), widget.size()));
+        for &child in children {
+            self.draw_widget(child, renderer);
         }
+        renderer.pop_clip();
     }
 
     fn check_index(&self, index: usize) {
@@ -305,7 +778,7 @@ impl WidgetTree {
     /// The root is drawn first, then its first child, its first child's children, then
     /// second child, and so on, recursively.
     /// `iter(0)` will iterate over all widgets
-    pub fn iter<'a>(&'a self, root: usize) -> impl Iterator<Item=&'a dyn Widget> {
+    pub fn iter(&self, root: usize) -> impl Iterator<Item=&dyn Widget> {
         self.check_index(root);
 
         WidgetIterator {
@@ -316,7 +789,7 @@ impl WidgetTree {
     }
 
     /// Iterates over widgets in this tree mutably in graph traversal order.  See `iter`
-    pub fn iter_mut<'a>(&'a mut self, root: usize) -> impl Iterator<Item=&'a mut dyn Widget> {
+    pub fn iter_mut(&mut self, root: usize) -> impl Iterator<Item=&mut dyn Widget> {
         self.check_index(root);
 
         WidgetIteratorMut {
@@ -393,7 +866,525 @@ impl<'a> Iterator for WidgetIteratorMut<'a> {
         // This should be safe because it should not be possible for repeated calls
         // to next to ever yield the same Widget twice
         unsafe {
-            Some(std::mem::transmute::<_, &'a mut (dyn Widget + 'static)>(result))
+            Some(std::mem::transmute::<&mut (dyn Widget + 'static),
+                &'a mut (dyn Widget + 'static)>(result))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::color::Color;
+    use crate::input::{Cursor, EventKind, Key, Modifiers, MouseButton, ScrollUnit};
+    use crate::render::{TextRun, Texture, UvRect};
+    use crate::theme_builder::ThemeBuilderSet;
+    use crate::widget::{Point, WidgetState};
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    fn tree() -> WidgetTree {
+        let themes = ThemeBuilderSet::default().create_theme_set().unwrap();
+        WidgetTree::new(EmptyWidget::new(), themes)
+    }
+
+    fn add(tree: &mut WidgetTree, parent: usize, focusable: bool) -> usize {
+        let mut widget = EmptyWidget::new();
+        widget.state_mut().set_focusable(focusable);
+        tree.add_child(parent, widget);
+        tree.widgets.len() - 1
+    }
+
+    fn key_pressed(key: Key, modifiers: Modifiers) -> Event {
+        Event { kind: EventKind::KeyPressed { key, modifiers }, cursor: Cursor { x: 0.0, y: 0.0 } }
+    }
+
+    fn mouse_event(kind: EventKind, x: f32, y: f32) -> Event {
+        Event { kind, cursor: Cursor { x, y } }
+    }
+
+    fn place(tree: &mut WidgetTree, index: usize, x: i32, y: i32, width: u32, height: u32) {
+        let state = tree[index].state_mut();
+        state.set_position(Point { x, y });
+        state.set_size(Size { width, height });
+    }
+
+    // root (0) 100 x 100
+    //  - below (1) at 0, 0, 50 x 50
+    //  - above (2) at 25, 25, 50 x 50, drawn over below
+    //     - inner (3) at 30, 30, 10 x 10
+    fn overlapping_tree() -> WidgetTree {
+        let mut tree = tree();
+        for parent in &[0, 0, 2] {
+            add(&mut tree, *parent, false);
+        }
+        place(&mut tree, 0, 0, 0, 100, 100);
+        place(&mut tree, 1, 0, 0, 50, 50);
+        place(&mut tree, 2, 25, 25, 50, 50);
+        place(&mut tree, 3, 30, 30, 10, 10);
+        tree
+    }
+
+    // logs each key pressed and capture callback as "phase:index"
+    fn log_key_events(tree: &mut WidgetTree, index: usize, log: &Log) {
+        let state = tree[index].state_mut();
+        let log_clone = Rc::clone(log);
+        state.set_capture_callback(Rc::new(move |tree, index, _| {
+            let phase = tree.event_context().unwrap().phase();
+            log_clone.borrow_mut().push(format!("{:?}:{}", phase, index));
+            false
+        }));
+        let log_clone = Rc::clone(log);
+        state.set_key_pressed_callback(Rc::new(move |tree, index, _| {
+            let phase = tree.event_context().unwrap().phase();
+            log_clone.borrow_mut().push(format!("{:?}:{}", phase, index));
+            false
+        }));
+    }
+
+    #[test]
+    fn events_capture_then_target_then_bubble() {
+        let mut tree = tree();
+        let parent = add(&mut tree, 0, false);
+        let target = add(&mut tree, parent, true);
+        let log = Log::default();
+        for index in &[0, parent, target] {
+            log_key_events(&mut tree, *index, &log);
+        }
+        tree.set_focus(Some(target));
+
+        tree.handle_event(key_pressed(Key::A, Modifiers::default()));
+
+        assert_eq!(*log.borrow(), vec![
+            "Capture:0", "Capture:1", "Target:2", "Bubble:1", "Bubble:0",
+        ]);
+        assert!(tree.event_context().is_none());
+    }
+
+    #[test]
+    fn unset_callbacks_do_not_stop_bubbling() {
+        let mut tree = tree();
+        let parent = add(&mut tree, 0, false);
+        let target = add(&mut tree, parent, true);
+        let log = Log::default();
+        log_key_events(&mut tree, 0, &log);
+        tree.set_focus(Some(target));
+
+        tree.handle_event(key_pressed(Key::A, Modifiers::default()));
+
+        assert_eq!(*log.borrow(), vec!["Capture:0", "Bubble:0"]);
+    }
+
+    #[test]
+    fn stop_propagation_skips_remaining_widgets() {
+        let mut tree = tree();
+        let parent = add(&mut tree, 0, false);
+        let target = add(&mut tree, parent, true);
+        let log = Log::default();
+        for index in &[0, parent, target] {
+            log_key_events(&mut tree, *index, &log);
+        }
+        let log_clone = Rc::clone(&log);
+        tree[target].state_mut().set_key_pressed_callback(Rc::new(move |tree, index, _| {
+            log_clone.borrow_mut().push(format!("stopped:{}", index));
+            tree.event_context_mut().unwrap().stop_propagation();
+            false
+        }));
+        tree.set_focus(Some(target));
+
+        tree.handle_event(key_pressed(Key::A, Modifiers::default()));
+
+        assert_eq!(*log.borrow(), vec!["Capture:0", "Capture:1", "stopped:2"]);
+    }
+
+    #[test]
+    fn handled_capture_stops_before_target() {
+        let mut tree = tree();
+        let parent = add(&mut tree, 0, false);
+        let target = add(&mut tree, parent, true);
+        let log = Log::default();
+        for index in &[0, parent, target] {
+            log_key_events(&mut tree, *index, &log);
+        }
+        tree[0].state_mut().set_capture_callback(Rc::new(|_, _, _| true));
+        tree.set_focus(Some(target));
+
+        tree.handle_event(key_pressed(Key::A, Modifiers::default()));
+
+        assert!(log.borrow().is_empty());
+    }
+
+    #[test]
+    fn scroll_bubbles_from_the_widget_under_the_cursor_until_handled() {
+        let mut tree = overlapping_tree();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let log_scroll = |tree: &mut WidgetTree, index: usize, handled: bool| {
+            let log = Rc::clone(&log);
+            tree[index].state_mut().set_mouse_scrolled_callback(Rc::new(move |_, index, delta| {
+                log.borrow_mut().push((index, delta));
+                handled
+            }));
+        };
+        for index in 0..4 {
+            log_scroll(&mut tree, index, false);
         }
+        let scroll = |tree: &mut WidgetTree, x, y| {
+            log.borrow_mut().clear();
+            let kind = EventKind::MouseScrolled { delta_x: 0.0, delta_y: -2.0,
+                                                  unit: ScrollUnit::Lines };
+            tree.handle_event(mouse_event(kind, x, y));
+            log.borrow().iter().map(|(index, _)| *index).collect::<Vec<_>>()
+        };
+
+        assert_eq!(scroll(&mut tree, 35.0, 35.0), vec![3, 2, 0]);
+        assert_eq!(log.borrow()[0], (3, (0.0, -2.0, ScrollUnit::Lines)));
+        assert_eq!(scroll(&mut tree, 10.0, 10.0), vec![1, 0]);
+
+        log_scroll(&mut tree, 2, true);
+        assert_eq!(scroll(&mut tree, 35.0, 35.0), vec![3, 2]);
+        assert_eq!(scroll(&mut tree, 60.0, 60.0), vec![2]);
+    }
+
+    #[test]
+    fn prevent_default_on_tab_keeps_focus() {
+        let mut tree = tree();
+        let first = add(&mut tree, 0, true);
+        let second = add(&mut tree, 0, true);
+        tree.set_focus(Some(first));
+
+        tree.handle_event(key_pressed(Key::Tab, Modifiers::default()));
+        assert_eq!(tree.focus(), Some(second));
+
+        tree[0].state_mut().set_key_pressed_callback(Rc::new(|tree, _, (key, _)| {
+            if key == Key::Tab {
+                tree.event_context_mut().unwrap().prevent_default();
+            }
+            false
+        }));
+        tree.handle_event(key_pressed(Key::Tab, Modifiers::default()));
+        assert_eq!(tree.focus(), Some(second));
+    }
+
+    #[test]
+    fn tab_and_shift_tab_wrap_around() {
+        let mut tree = tree();
+        let first = add(&mut tree, 0, true);
+        let second = add(&mut tree, first, true);
+        let third = add(&mut tree, 0, true);
+        let tab = || key_pressed(Key::Tab, Modifiers::default());
+        let shift_tab = || key_pressed(Key::Tab, Modifiers { shift: true, ..Default::default() });
+
+        tree.set_focus(Some(third));
+        tree.handle_event(tab());
+        assert_eq!(tree.focus(), Some(first));
+        tree.handle_event(tab());
+        assert_eq!(tree.focus(), Some(second));
+
+        tree.set_focus(Some(first));
+        tree.handle_event(shift_tab());
+        assert_eq!(tree.focus(), Some(third));
+        tree.handle_event(shift_tab());
+        assert_eq!(tree.focus(), Some(second));
+    }
+
+    #[test]
+    fn tab_skips_widgets_that_are_not_focusable() {
+        let mut tree = tree();
+        let first = add(&mut tree, 0, true);
+        let skipped = add(&mut tree, 0, false);
+        add(&mut tree, skipped, false);
+        let last = add(&mut tree, skipped, true);
+
+        tree.set_focus(Some(first));
+        tree.handle_event(key_pressed(Key::Tab, Modifiers::default()));
+        assert_eq!(tree.focus(), Some(last));
+        tree.handle_event(key_pressed(Key::Tab, Modifiers::default()));
+        assert_eq!(tree.focus(), Some(first));
+
+        tree.set_focus(Some(skipped));
+        assert_eq!(tree.focus(), Some(first));
+    }
+
+    #[test]
+    fn focus_lost_fires_before_focus_gained() {
+        let mut tree = tree();
+        let first = add(&mut tree, 0, true);
+        let second = add(&mut tree, 0, true);
+
+        let log: Log = Rc::default();
+        for &index in &[first, second] {
+            let log_clone = Rc::clone(&log);
+            tree[index].state_mut().set_focus_gained_callback(Rc::new(move |tree, index, _| {
+                log_clone.borrow_mut().push(format!("gained:{}:{:?}", index, tree.focus()));
+                false
+            }));
+            let log_clone = Rc::clone(&log);
+            tree[index].state_mut().set_focus_lost_callback(Rc::new(move |tree, index, _| {
+                log_clone.borrow_mut().push(format!("lost:{}:{:?}", index, tree.focus()));
+                false
+            }));
+        }
+
+        tree.set_focus(Some(first));
+        tree.handle_event(key_pressed(Key::Tab, Modifiers::default()));
+        assert_eq!(*log.borrow(), vec![
+            format!("gained:{}:Some({})", first, first),
+            format!("lost:{}:None", first),
+            format!("gained:{}:Some({})", second, second),
+        ]);
+    }
+
+    widget!{
+        // records its index each time it is laid out
+        #[derive(Default)]
+        pub struct LayoutCounter { log: Rc<RefCell<Vec<usize>>> }
+
+        fn layout(&mut self) {
+            self.log.borrow_mut().push(self.index());
+        }
+    }
+
+    const LAYOUT_THEMES: &str = "
+themes:
+  root:
+    relative: { width: Parent, height: Parent }
+  column:
+    layout: BoxVertical
+    size: [20, 0]
+    relative: { height: ChildSum }
+  panel:
+    size: [30, 30]
+  small:
+    size: [10, 10]
+  tall:
+    size: [10, 30]
+";
+
+    // root
+    //  - column (1), sized by its children
+    //     - label (2)
+    //     - counter (3)
+    //  - panel (4), a fixed size
+    //     - counter (5)
+    fn layout_tree() -> (WidgetTree, Rc<RefCell<Vec<usize>>>) {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let counter = |theme: &str| {
+            let mut widget = LayoutCounter { log: Rc::clone(&log), ..Default::default() };
+            widget.set_theme(theme);
+            widget
+        };
+
+        let builder: ThemeBuilderSet = serde_yaml::from_str(LAYOUT_THEMES).unwrap();
+        let mut tree = WidgetTree::new(counter("root"), builder.create_theme_set().unwrap());
+        tree.set_size(Size { width: 100, height: 100 });
+        tree.add_child(0, counter("column"));
+        let mut label = Label::new("text".to_string());
+        label.set_theme("small");
+        tree.add_child(1, label);
+        tree.add_child(1, counter("small"));
+        tree.add_child(0, counter("panel"));
+        tree.add_child(4, counter("small"));
+
+        tree.layout();
+        assert_eq!(*log.borrow(), vec![3, 1, 5, 4, 0]);
+        log.borrow_mut().clear();
+        (tree, log)
+    }
+
+    fn laid_out(tree: &mut WidgetTree, log: &Rc<RefCell<Vec<usize>>>) -> Vec<usize> {
+        tree.layout();
+        let mut indices = log.borrow_mut().split_off(0);
+        indices.sort();
+        indices
+    }
+
+    fn size(tree: &WidgetTree, index: usize) -> (u32, u32) {
+        let size = tree[index].size();
+        (size.width, size.height)
+    }
+
+    #[test]
+    fn layout_without_changes_does_nothing() {
+        let (mut tree, log) = layout_tree();
+        assert!(laid_out(&mut tree, &log).is_empty());
+    }
+
+    #[test]
+    fn set_text_lays_out_ancestors_sized_by_children() {
+        let (mut tree, log) = layout_tree();
+        tree[2].as_any_mut().downcast_mut::<Label>().unwrap().set_text("changed");
+
+        assert_eq!(laid_out(&mut tree, &log), vec![1, 3]);
+        assert!(tree.iter(0).all(|widget| !widget.state().is_layout_dirty()));
+    }
+
+    #[test]
+    fn set_theme_lays_out_only_the_changed_subtree() {
+        let (mut tree, log) = layout_tree();
+        let set_theme = |tree: &mut WidgetTree, index: usize, theme: &str| {
+            tree[index].as_any_mut().downcast_mut::<LayoutCounter>().unwrap().set_theme(theme);
+        };
+
+        set_theme(&mut tree, 5, "tall");
+        assert_eq!(laid_out(&mut tree, &log), vec![5]);
+        assert_eq!(size(&tree, 5), (10, 30));
+
+        set_theme(&mut tree, 3, "tall");
+        assert_eq!(laid_out(&mut tree, &log), vec![1, 3]);
+        assert_eq!(size(&tree, 1), (20, 40));
+    }
+
+    #[test]
+    fn remove_lays_out_the_parent() {
+        let (mut tree, log) = layout_tree();
+        assert_eq!(size(&tree, 1), (20, 20));
+
+        tree.remove(3);
+        assert_eq!(laid_out(&mut tree, &log), vec![1]);
+        assert_eq!(size(&tree, 1), (20, 10));
+
+        tree.remove(5);
+        assert_eq!(laid_out(&mut tree, &log), vec![4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unable to remove the root widget")]
+    fn remove_root_panics() {
+        let (mut tree, _) = layout_tree();
+        tree.remove(0);
+    }
+
+    #[test]
+    fn new_adds_theme_images() {
+        let file = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/panel.yml");
+        let themes = ThemeBuilderSet::load(file).unwrap().create_theme_set().unwrap();
+        let tree = WidgetTree::new(EmptyWidget::new(), themes);
+
+        assert!(tree.images().texture("colors").is_some());
+        for id in &["frame", "red", "green", "blue"] {
+            assert!(tree.images().contains(id));
+        }
+    }
+
+    #[test]
+    fn new_adds_theme_fonts() {
+        let file = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fonts/fonts.yml");
+        let themes = ThemeBuilderSet::load(file).unwrap().create_theme_set().unwrap();
+        let tree = WidgetTree::new(EmptyWidget::new(), themes);
+
+        assert!(tree.fonts().contains("bitmap"));
+        assert_eq!(tree.fonts().font("bitmap").advance('A'), 5.0);
+    }
+
+    // logs each push_clip as "x,y,width,height" and each pop_clip as "pop"
+    #[derive(Default)]
+    struct ClipRecorder {
+        log: Vec<String>,
+    }
+
+    impl Renderer for ClipRecorder {
+        fn fill_rect(&mut self, _rect: Rect, _color: Color) {}
+
+        fn draw_quad(&mut self, _texture: &Texture, _rect: Rect, _uv: UvRect, _color: Color) {}
+
+        fn draw_text(&mut self, _run: &TextRun) {}
+
+        fn push_clip(&mut self, rect: Rect) {
+            self.log.push(format!("{},{},{},{}", rect.x, rect.y, rect.width, rect.height));
+        }
+
+        fn pop_clip(&mut self) {
+            self.log.push("pop".to_string());
+        }
+    }
+
+    #[test]
+    fn draw_clips_children_to_their_parent() {
+        let mut tree = tree();
+        let parent = add(&mut tree, 0, false);
+        add(&mut tree, parent, false);
+        add(&mut tree, 0, false);
+
+        tree[0].state_mut().set_size(Size { width: 100, height: 80 });
+        tree[parent].state_mut().set_position(Point { x: 10, y: 20 });
+        tree[parent].state_mut().set_size(Size { width: 30, height: 40 });
+
+        let mut renderer = ClipRecorder::default();
+        tree.draw(&mut renderer);
+        assert_eq!(renderer.log, vec!["0,0,100,80", "10,20,30,40", "pop", "pop"]);
+
+        // hidden widgets and their children are not drawn
+        tree[parent].state_mut().set_visible(false);
+        let mut renderer = ClipRecorder::default();
+        tree.draw(&mut renderer);
+        assert_eq!(renderer.log, vec!["0,0,100,80", "pop"]);
+    }
+
+    #[test]
+    fn hover_fires_exited_then_entered_on_changed_widgets() {
+        let mut tree = overlapping_tree();
+        let log: Log = Rc::default();
+        for index in 0..4 {
+            let log_clone = Rc::clone(&log);
+            tree[index].state_mut().set_mouse_entered_callback(Rc::new(move |_, index, _| {
+                log_clone.borrow_mut().push(format!("entered:{}", index));
+                false
+            }));
+            let log_clone = Rc::clone(&log);
+            tree[index].state_mut().set_mouse_exited_callback(Rc::new(move |_, index, _| {
+                log_clone.borrow_mut().push(format!("exited:{}", index));
+                false
+            }));
+        }
+        let move_to = |tree: &mut WidgetTree, x, y| {
+            log.borrow_mut().clear();
+            tree.handle_event(mouse_event(EventKind::MouseMoved { delta_x: 0.0, delta_y: 0.0 },
+                                          x, y));
+            log.borrow().clone()
+        };
+
+        assert_eq!(move_to(&mut tree, 10.0, 10.0), vec!["entered:0", "entered:1"]);
+        assert_eq!(move_to(&mut tree, 35.0, 35.0), vec!["exited:1", "entered:2", "entered:3"]);
+        assert_eq!(move_to(&mut tree, 45.0, 45.0), vec!["exited:3"]);
+        assert!(move_to(&mut tree, 46.0, 46.0).is_empty());
+        assert_eq!(move_to(&mut tree, 35.0, 35.0), vec!["entered:3"]);
+        assert_eq!(move_to(&mut tree, 200.0, 200.0), vec!["exited:3", "exited:2", "exited:0"]);
+        assert!(move_to(&mut tree, 300.0, 300.0).is_empty());
+    }
+
+    #[test]
+    fn clicks_go_to_the_topmost_visible_widget() {
+        let mut tree = overlapping_tree();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for index in 0..4 {
+            let log = Rc::clone(&log);
+            tree[index].state_mut().set_mouse_pressed_callback(Rc::new(move |_, index, _| {
+                log.borrow_mut().push(index);
+                false
+            }));
+        }
+        let click = |tree: &mut WidgetTree, x, y| {
+            log.borrow_mut().clear();
+            tree.handle_event(mouse_event(EventKind::MousePressed { button: MouseButton::Left },
+                                          x, y));
+            log.borrow().clone()
+        };
+
+        assert_eq!(click(&mut tree, 45.0, 45.0), vec![2, 0]);
+        assert_eq!(click(&mut tree, 35.0, 35.0), vec![3, 2, 0]);
+        assert_eq!(click(&mut tree, 10.0, 10.0), vec![1, 0]);
+        assert_eq!(tree.widget_at(&Cursor { x: 60.0, y: 60.0 }), Some(2));
+
+        tree[2].state_mut().set_visible(false);
+        assert_eq!(click(&mut tree, 45.0, 45.0), vec![1, 0]);
+        assert_eq!(click(&mut tree, 35.0, 35.0), vec![1, 0]);
+        assert_eq!(tree.widget_at(&Cursor { x: 60.0, y: 60.0 }), Some(0));
+
+        tree[0].state_mut().set_visible(false);
+        assert_eq!(click(&mut tree, 10.0, 10.0), Vec::<usize>::new());
     }
 }